@@ -0,0 +1,68 @@
+// packs the assets directory into a tar archive, which the game embeds
+use std::fs;
+use std::path::Path;
+
+fn main() {
+  let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+  println!("cargo:rerun-if-changed=assets");
+
+  // the files of all subdirectories too, like `AssetPack::from_dir` reads them
+  let mut paths = Vec::new();
+  let mut dirs = vec![assets.clone()];
+  while let Some(dir) = dirs.pop() {
+    let entries =
+      fs::read_dir(&dir).unwrap_or_else(|e| panic!("can't read {}: {}", dir.display(), e));
+    for entry in entries {
+      let path = entry
+        .unwrap_or_else(|e| panic!("can't read {}: {}", dir.display(), e))
+        .path();
+      if path.is_dir() {
+        dirs.push(path);
+      } else {
+        paths.push(path);
+      }
+    }
+  }
+  // the same archive for the same files
+  paths.sort();
+
+  let mut tar = Vec::new();
+  for path in paths {
+    let name = path
+      .strip_prefix(&assets)
+      .expect("asset paths are under the assets directory")
+      .to_string_lossy();
+    let name = name.replace('\\', "/");
+    let data = fs::read(&path).unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e));
+    tar.extend(header(&name, data.len()));
+    tar.extend(&data);
+    tar.resize(tar.len().div_ceil(512) * 512, 0);
+  }
+  tar.resize(tar.len() + 1024, 0);
+
+  let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+  let out_path = Path::new(&out_dir).join("assets.tar");
+  fs::write(&out_path, tar).unwrap_or_else(|e| panic!("can't write {}: {}", out_path.display(), e));
+}
+
+// a ustar header for a regular file
+fn header(name: &str, size: usize) -> Vec<u8> {
+  assert!(name.len() < 100, "asset file name too long: {}", name);
+  let mut header = vec![0; 512];
+  let mut field = |offset: usize, text: &str| {
+    header[offset..offset + text.len()].copy_from_slice(text.as_bytes());
+  };
+  field(0, name);
+  field(100, "0000644");
+  field(108, "0000000");
+  field(116, "0000000");
+  field(124, &format!("{:011o}", size));
+  field(136, "00000000000");
+  field(156, "0");
+  field(257, "ustar\x0000");
+  // the checksum is computed with its own field as spaces
+  header[148..156].copy_from_slice(b"        ");
+  let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+  header[148..155].copy_from_slice(format!("{:06o}\0", checksum).as_bytes());
+  header
+}
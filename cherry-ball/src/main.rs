@@ -2,82 +2,299 @@ use cgmath::prelude::*;
 use game_lib::types::*;
 use game_lib::*;
 use rand::prelude::*;
-use sdl2::event::Event;
+use sdl2::controller::{Axis, Button};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
 
 fn main() {
   let control_mode = get_arg("control")
     .map(|s| match s.as_str() {
       "toggle" => Some(ControlScheme::Toggle),
       "hold" => Some(ControlScheme::Hold),
+      "drag" => Some(ControlScheme::Drag),
       _ => None,
     })
     .flatten()
-    .unwrap_or(ControlScheme::Toggle);
+    .unwrap_or(DEFAULT_CONTROL_SCHEME);
 
   let scale = get_arg("scale")
     .map(|s| s.parse().ok())
     .flatten()
     .unwrap_or(8);
 
-  game_lib::run(
-    cell_phone_config(scale),
-    State::new(control_mode),
-    init,
-    update,
-    render,
-    handle_event,
-  );
+  let mut config = cell_phone_config(scale);
+  if get_arg("keypad").as_deref() == Some("on") {
+    config = config.on_screen_keypad();
+  }
+
+  let result = game_lib::run_game_with(config, |gcontext| {
+    Ok(TitleScene {
+      control_scheme: control_mode,
+      art: init(gcontext)?,
+    })
+  });
+  if let Err(e) = result {
+    eprintln!("cherry ball: {}", e);
+    std::process::exit(1);
+  }
 }
 
-fn init(gcontext: &mut GContext) {
-  let palette: Palette = [('#', DARK_COLOR), ('_', BRIGHT_COLOR)]
-    .iter()
-    .cloned()
-    .collect();
+// the surfaces added by `init`, passed on from scene to scene
+#[derive(Copy, Clone)]
+struct Art {
+  ball: SurfaceId,
+  // squashed against the paddle or a side wall, and against the top or bottom
+  ball_squash_x: SurfaceId,
+  ball_squash_y: SurfaceId,
+  cherry: SurfaceId,
+  coin: SurfaceId,
+  coin_turned: SurfaceId,
+  coin_edge: SurfaceId,
+}
+
+// the assets directory, packed by build.rs
+const EMBEDDED_ASSETS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/assets.tar"));
+
+// --assets can point at a directory or a tar archive, otherwise debug builds read the source
+// tree, so the art can be edited while the game runs, and release builds the embedded pack
+fn asset_pack() -> Result<AssetPack, PackError> {
+  match get_arg("assets") {
+    Some(path) => AssetPack::load(path),
+    None if cfg!(all(debug_assertions, not(target_os = "emscripten"))) => {
+      AssetPack::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets"))
+    }
+    None => AssetPack::from_tar(EMBEDDED_ASSETS),
+  }
+}
+
+const NEW_GAME_TRANSITION: Transition = Transition::Wipe { ticks: 6 };
+const GAME_OVER_TRANSITION: Transition = Transition::Fade { ticks: 8 };
+
+struct TitleScene {
+  control_scheme: ControlScheme,
+  art: Art,
+}
+
+impl GameState for TitleScene {
+  fn init(&mut self, gcontext: &mut GContext) {
+    gcontext.play_sfx(&sfx("title"));
+  }
+
+  fn render(&self, gcontext: &mut GContext) {
+    gcontext.draw_text_box(
+      HorPos::Center,
+      VertPos::Center,
+      &["cherry ball", "", "press a key"],
+      BRIGHT_COLOR,
+    );
+  }
+
+  fn update(&mut self, ctx: &mut UpdateContext) {
+    if ctx.key_status.was_any_just_pressed() {
+      let play_scene = PlayScene::new(self.control_scheme, self.art, ctx.rng);
+      ctx.replace_scene(play_scene, NEW_GAME_TRANSITION);
+    }
+  }
+}
+
+impl GameState for PlayScene {
+  fn update(&mut self, ctx: &mut UpdateContext) {
+    update(self, ctx);
+  }
+
+  fn render(&self, gcontext: &mut GContext) {
+    render(gcontext, self);
+  }
+}
+
+struct PauseScene;
 
-  #[rustfmt::skip]
-  gcontext.add_surface(
-    surface("ball"),
-    &palette,
+impl GameState for PauseScene {
+  fn render(&self, gcontext: &mut GContext) {
+    gcontext.draw_text_box(HorPos::Center, VertPos::Center, &["paused"], BRIGHT_COLOR);
+  }
+
+  fn update(&mut self, ctx: &mut UpdateContext) {
+    if ctx.actions.was_just_pressed(&action("pause")) {
+      ctx.pop_scene(Transition::None);
+    }
+  }
+
+  fn is_overlay(&self) -> bool {
+    true
+  }
+}
+
+struct GameOverScene {
+  control_scheme: ControlScheme,
+  art: Art,
+  score: i32,
+  selected: usize,
+  // measured in `init` with the font the entries are drawn in, for taps
+  entry_rects: Vec<(i32, i32, u32, u32)>,
+}
+
+const GAME_OVER_MENU: [&str; 2] = ["play again", "title"];
+
+// the box of a menu entry, its text starts a pixel in
+fn menu_entry_rect(font: &Font, ix: usize) -> (i32, i32, u32, u32) {
+  let w = font.text_width(GAME_OVER_MENU[ix]) + 1;
+  let h = font.line_height() + 2;
+  ((84 - w as i32) / 2, 24 + ix as i32 * 9, w, h)
+}
+
+impl GameOverScene {
+  fn new(control_scheme: ControlScheme, art: Art, score: i32) -> GameOverScene {
+    GameOverScene {
+      control_scheme,
+      art,
+      score,
+      selected: 0,
+      entry_rects: Vec::new(),
+    }
+  }
+
+  fn choose(&self, ctx: &mut UpdateContext, ix: usize) {
+    if ix == 0 {
+      let play_scene = PlayScene::new(self.control_scheme, self.art, ctx.rng);
+      ctx.replace_scene(play_scene, NEW_GAME_TRANSITION);
+    } else {
+      let title_scene = TitleScene {
+        control_scheme: self.control_scheme,
+        art: self.art,
+      };
+      ctx.replace_scene(title_scene, GAME_OVER_TRANSITION);
+    }
+  }
+}
+
+impl GameState for GameOverScene {
+  fn init(&mut self, gcontext: &mut GContext) {
+    gcontext.play_sfx(&sfx("game_over"));
+    let font = gcontext.assets.default_font();
+    self.entry_rects = (0..GAME_OVER_MENU.len())
+      .map(|ix| menu_entry_rect(font, ix))
+      .collect();
+  }
+
+  fn render(&self, gcontext: &mut GContext) {
+    gcontext.draw_text_box(
+      HorPos::Center,
+      VertPos::Abs { y: 4 },
+      &["game over".to_string(), format!("score: {}", self.score)],
+      BRIGHT_COLOR,
+    );
+    // the selected entry is drawn inverted
+    for (ix, (entry, &(x, y, _, _))) in GAME_OVER_MENU.iter().zip(&self.entry_rects).enumerate() {
+      if ix == self.selected {
+        gcontext.draw_text_colored(x + 1, y + 1, entry, BRIGHT_COLOR, Some(DARK_COLOR));
+      } else {
+        gcontext.draw_text(x + 1, y + 1, entry);
+      }
+    }
+  }
+
+  fn update(&mut self, ctx: &mut UpdateContext) {
+    let entries = GAME_OVER_MENU.len();
+    if ctx.actions.was_just_pressed(&action("paddle_up")) {
+      self.selected = (self.selected + entries - 1) % entries;
+    }
+    if ctx.actions.was_just_pressed(&action("paddle_down")) {
+      self.selected = (self.selected + 1) % entries;
+    }
+    if ctx.actions.was_just_pressed(&action("select")) {
+      self.choose(ctx, self.selected);
+    } else if let Some(tap) = ctx.key_status.tap() {
+      let tapped = self.entry_rects.iter().position(|&(x, y, w, h)| {
+        (x..x + w as i32).contains(&tap.x) && (y..y + h as i32).contains(&tap.y)
+      });
+      if let Some(ix) = tapped {
+        self.choose(ctx, ix);
+      }
+    }
+  }
+}
+
+fn init(gcontext: &mut GContext) -> Result<Art, GameError> {
+  gcontext.set_window_title("cherry ball");
+
+  let input_map = &mut gcontext.input_map;
+  input_map.set_bindings(
+    action("paddle_up"),
     vec![
-      "  ##  ",
-      " #_## ",
-      "#_####",
-      "######",
-      " #### ",
-      "  ##  ",
+      Keycode::W.into(),
+      Keycode::Up.into(),
+      PhoneKey::Num2.into(),
+      Button::DPadUp.into(),
+      Input::GamepadAxis(Axis::LeftY, AxisDirection::Negative),
     ],
   );
-  #[rustfmt::skip]
-  gcontext.add_surface(
-    surface("cherry"),
-    &palette,
+  input_map.set_bindings(
+    action("paddle_down"),
     vec![
-      "  #     ",
-      " # #    ",
-      " #  #   ",
-      " #   ## ",
-      " ## #_##",
-      "#_## ###",
-      "#### ## ",
-      " ##     ",
+      Keycode::S.into(),
+      Keycode::Down.into(),
+      PhoneKey::Num8.into(),
+      Button::DPadDown.into(),
+      Input::GamepadAxis(Axis::LeftY, AxisDirection::Positive),
     ],
   );
-  #[rustfmt::skip]
-  gcontext.add_surface(
-    surface("coin"),
-    &palette,
+  input_map.set_bindings(
+    action("select"),
     vec![
-      "  ####  ",
-      " ##___# ",
-      "##__#__#",
-      "##__#__#",
-      "##__#__#",
-      "##__#__#",
-      " ##___# ",
-      "  ####  ",
+      Keycode::Return.into(),
+      Keycode::Space.into(),
+      PhoneKey::Num5.into(),
+      PhoneKey::Call.into(),
+      Button::A.into(),
     ],
   );
+  input_map.set_bindings(
+    action("pause"),
+    vec![
+      Keycode::Escape.into(),
+      Keycode::P.into(),
+      PhoneKey::SoftRight.into(),
+      Button::Start.into(),
+    ],
+  );
+  // a missing bindings file is created with the defaults, so there is something to edit
+  if let Some(path) = get_arg("bindings") {
+    if std::path::Path::new(&path).exists() {
+      input_map.load_bindings(&path)?;
+    } else {
+      input_map.save_bindings(&path)?;
+    }
+  }
+
+  // ascii surfaces and tunes, debug builds reload the surfaces when they change
+  gcontext.add_asset_pack(&asset_pack()?)?;
+  let load = |name: &str| gcontext.assets.surface_id(&surface(name));
+  let art = Art {
+    ball: load("ball")?,
+    ball_squash_x: load("ball_squash_x")?,
+    ball_squash_y: load("ball_squash_y")?,
+    cherry: load("cherry")?,
+    coin: load("coin")?,
+    coin_turned: load("coin_turned")?,
+    coin_edge: load("coin_edge")?,
+  };
+
+  gcontext.add_sfx(sfx("hit"), Sound::tone(440.0, 30));
+  gcontext.add_sfx(
+    sfx("cherry"),
+    Sound::sequence(vec![Note::new(1319.0, 40), Note::new(1760.0, 60)]),
+  );
+  gcontext.add_sfx(
+    sfx("coin"),
+    Sound::sequence(vec![
+      Note::new(988.0, 50),
+      Note::new(1319.0, 50),
+      Note::new(1976.0, 120),
+    ])
+    .duty(0.25),
+  );
+  Ok(art)
 }
 
 #[derive(Copy, Clone)]
@@ -101,30 +318,42 @@ impl Age {
   }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 struct Item {
   item_type: ItemType,
   velocity: V2F,
   pos: V2F,
   age: Age,
+  animation: AnimationPlayer,
 }
 
 impl Item {
+  fn new(art: Art, item_type: ItemType, velocity: V2F, pos: V2F, age: Age) -> Item {
+    Item {
+      item_type,
+      velocity,
+      pos,
+      age,
+      animation: AnimationPlayer::new(item_animation(art, item_type)),
+    }
+  }
+
   fn rect(&self) -> Rect {
     Rect::new(self.pos.x, self.pos.y, ITEM_SIZE as f32, ITEM_SIZE as f32)
   }
 }
 
-struct State {
-  rng: rand::prelude::ThreadRng,
-
+struct PlayScene {
   control_scheme: ControlScheme,
+  art: Art,
 
   paddle_pos: P2F,
   paddle_dir: f32,
 
   ball_pos: P2F,
   ball_dir: V2F,
+  // drawn instead of the ball until it completes
+  ball_squash: Option<AnimationPlayer>,
 
   rings: Vec<(f32, V2F)>,
 
@@ -137,31 +366,55 @@ const BALL_SIZE: u32 = 6;
 const ITEM_SPEED: f32 = 3.0;
 const ITEM_SIZE: u32 = 8;
 const PADDLE_SPEED: i32 = 2;
+const DRAG_SPEED: i32 = 4;
 const PADDLE_SIZE: V2U = V2U::new(3, 10);
 const D_MAX: f32 = BALL_SIZE as f32 / 2.0 + PADDLE_SIZE.y as f32 / 2.0;
 const COIN_LIFETIME: u32 = 100;
 const COIN_FLASH_THRESHOLD: u32 = 20;
+const SQUASH_TICKS: u32 = 2;
+
+fn item_animation(art: Art, item_type: ItemType) -> Animation {
+  match item_type {
+    ItemType::Cherry => Animation::new(PlayMode::Loop).surface(art.cherry, 1),
+    ItemType::Coin => Animation::new(PlayMode::PingPong)
+      .surface(art.coin, 4)
+      .surface(art.coin_turned, 2)
+      .surface(art.coin_edge, 2),
+  }
+}
+
+fn squash(surface_id: SurfaceId) -> Option<AnimationPlayer> {
+  let animation = Animation::new(PlayMode::Once).surface(surface_id, SQUASH_TICKS);
+  Some(AnimationPlayer::new(animation))
+}
 
 #[derive(Copy, Clone, PartialEq)]
 enum ControlScheme {
   Hold,
   Toggle,
+  // the paddle follows the mouse or finger while it is down
+  Drag,
 }
 
-impl State {
-  fn new(control_scheme: ControlScheme) -> State {
-    let mut rng = rand::thread_rng();
-    let ball_dir_y = rng.gen_range(0.5..1.0);
-    State {
-      rng,
+// the web build is mostly played on phones
+#[cfg(target_os = "emscripten")]
+const DEFAULT_CONTROL_SCHEME: ControlScheme = ControlScheme::Drag;
+#[cfg(not(target_os = "emscripten"))]
+const DEFAULT_CONTROL_SCHEME: ControlScheme = ControlScheme::Toggle;
 
+impl PlayScene {
+  fn new(control_scheme: ControlScheme, art: Art, rng: &mut GameRng) -> PlayScene {
+    let ball_dir_y = rng.gen_range(0.5..1.0);
+    PlayScene {
       control_scheme,
+      art,
 
       paddle_pos: P2F::new(1.0, 1.0),
       paddle_dir: 0.0,
 
       ball_pos: P2F::new(0.0, 20.0),
       ball_dir: V2F::new(1.0, ball_dir_y).normalize() * BALL_SPEED,
+      ball_squash: None,
 
       rings: Vec::new(),
 
@@ -171,15 +424,49 @@ impl State {
   }
 }
 
-fn update(state: &mut State, key_status: &KeyStatus, game_tick_counter: u32) {
+fn update(state: &mut PlayScene, ctx: &mut UpdateContext) {
+  if ctx.actions.was_just_pressed(&action("pause")) {
+    ctx.push_scene(PauseScene, Transition::None);
+    return;
+  }
+
   // update paddle
-  if state.control_scheme == ControlScheme::Hold {
-    if key_status.is_key_pressed(sdl2::keyboard::Keycode::W) {
-      state.paddle_dir = -PADDLE_SPEED as f32;
-    } else if key_status.is_key_pressed(sdl2::keyboard::Keycode::S) {
-      state.paddle_dir = PADDLE_SPEED as f32;
-    } else {
-      state.paddle_dir = 0.0;
+  match state.control_scheme {
+    ControlScheme::Hold => {
+      if ctx.actions.is_pressed(&action("paddle_up")) {
+        state.paddle_dir = -PADDLE_SPEED as f32;
+      } else if ctx.actions.is_pressed(&action("paddle_down")) {
+        state.paddle_dir = PADDLE_SPEED as f32;
+      } else {
+        state.paddle_dir = 0.0;
+      }
+    }
+    ControlScheme::Toggle => {
+      if ctx.actions.was_just_pressed(&action("paddle_up")) {
+        if state.paddle_dir >= 0.0 {
+          state.paddle_dir = -PADDLE_SPEED as f32;
+        } else {
+          state.paddle_dir = 0.0;
+        }
+      }
+      if ctx.actions.was_just_pressed(&action("paddle_down")) {
+        if state.paddle_dir <= 0.0 {
+          state.paddle_dir = PADDLE_SPEED as f32;
+        } else {
+          state.paddle_dir = 0.0;
+        }
+      }
+    }
+    ControlScheme::Drag => {
+      state.paddle_dir = match ctx.key_status.pointer() {
+        Some(pointer) if ctx.key_status.is_key_pressed(MouseButton::Left) => {
+          let target_y = pointer.y as f32 - PADDLE_SIZE.y as f32 / 2.0;
+          (target_y - state.paddle_pos.y)
+            .max(-DRAG_SPEED as f32)
+            .min(DRAG_SPEED as f32)
+        }
+        _ => 0.0,
+      };
     }
   }
   state.paddle_pos.y += state.paddle_dir;
@@ -193,22 +480,33 @@ fn update(state: &mut State, key_status: &KeyStatus, game_tick_counter: u32) {
   }
 
   // update ball
+  if let Some(ball_squash) = &mut state.ball_squash {
+    if ball_squash.tick() {
+      state.ball_squash = None;
+    }
+  }
   state.ball_pos += state.ball_dir;
   if state.ball_pos.x < 0.0 {
-    // TODO game over
-    *state = State::new(state.control_scheme);
+    ctx.replace_scene(
+      GameOverScene::new(state.control_scheme, state.art, state.score),
+      GAME_OVER_TRANSITION,
+    );
+    return;
   }
   if state.ball_pos.x + BALL_SIZE as f32 >= 84.0 {
     state.ball_pos.x = 84.0 - BALL_SIZE as f32;
     state.ball_dir.x *= -1.0;
+    state.ball_squash = squash(state.art.ball_squash_x);
   }
   if state.ball_pos.y < 0.0 {
     state.ball_pos.y = 0.0;
     state.ball_dir.y *= -1.0;
+    state.ball_squash = squash(state.art.ball_squash_y);
   }
   if state.ball_pos.y + BALL_SIZE as f32 >= 48.0 {
     state.ball_pos.y = 48.0 - BALL_SIZE as f32;
     state.ball_dir.y *= -1.0;
+    state.ball_squash = squash(state.art.ball_squash_y);
   }
   let ball_rect = Rect::new(
     state.ball_pos.x,
@@ -233,51 +531,44 @@ fn update(state: &mut State, key_status: &KeyStatus, game_tick_counter: u32) {
     .normalize()
       * BALL_SPEED;
     state.ball_pos.x = PADDLE_SIZE.x as f32 + 1.0;
+    state.ball_squash = squash(state.art.ball_squash_x);
+    ctx.play_sfx(sfx("hit"));
   }
 
   // update rings
   for ring in &mut state.rings {
     ring.0 += 1.0;
   }
-  state.rings = state
-    .rings
-    .iter()
-    .filter(|(ring_size, _)| *ring_size < 10.0)
-    .map(|x| *x)
-    .collect();
+  state.rings.retain(|(ring_size, _)| *ring_size < 10.0);
 
   // update items
-  if game_tick_counter % 20 == 0 {
-    if state.items.len() < 3 {
-      let (item_type, age) = if state.rng.gen_range(0..10) == 0 {
-        (
-          ItemType::Coin,
-          Age::Finite {
-            remaining: COIN_LIFETIME as i32,
-          },
-        )
-      } else {
-        (ItemType::Cherry, Age::Infinite)
-      };
-      state.items.push(Item {
-        item_type,
-        velocity: V2F::new(
-          state.rng.gen_range(-0.5..0.5),
-          state.rng.gen_range(0.0..0.5) + 0.5,
-        )
-        .normalize()
-          * (ITEM_SPEED * state.rng.gen_range(0.5..1.5)),
-        pos: V2F::new(state.rng.gen_range(10..80) as f32, -5.0),
-        age,
-      });
-    }
+  if ctx.tick.is_multiple_of(20) && state.items.len() < 3 {
+    let (item_type, age) = if ctx.rng.gen_range(0..10) == 0 {
+      (
+        ItemType::Coin,
+        Age::Finite {
+          remaining: COIN_LIFETIME as i32,
+        },
+      )
+    } else {
+      (ItemType::Cherry, Age::Infinite)
+    };
+    let velocity = V2F::new(
+      ctx.rng.gen_range(-0.5..0.5),
+      ctx.rng.gen_range(0.0..0.5) + 0.5,
+    )
+    .normalize()
+      * (ITEM_SPEED * ctx.rng.gen_range(0.5..1.5));
+    let pos = V2F::new(ctx.rng.gen_range(10..80) as f32, -5.0);
+    let item = Item::new(state.art, item_type, velocity, pos, age);
+    state.items.push(item);
   }
   for item in &mut state.items {
     item.pos += item.velocity;
     item.velocity *= 0.9;
-    match &mut item.age {
-      Age::Finite { remaining } => *remaining -= 1,
-      _ => (),
+    item.animation.tick();
+    if let Age::Finite { remaining } = &mut item.age {
+      *remaining -= 1;
     }
   }
   let touched_items = state.items.iter().filter(|&item| {
@@ -288,13 +579,22 @@ fn update(state: &mut State, key_status: &KeyStatus, game_tick_counter: u32) {
   });
   state.score += touched_items
     .clone()
-    .map(|&item| match item.item_type {
+    .map(|item| match item.item_type {
       ItemType::Cherry => 1,
       ItemType::Coin => 5,
     })
     .sum::<i32>();
+  // the beeper plays one sound at a time, coins win
+  if touched_items
+    .clone()
+    .any(|item| matches!(item.item_type, ItemType::Coin))
+  {
+    ctx.play_sfx(sfx("coin"));
+  } else if touched_items.clone().next().is_some() {
+    ctx.play_sfx(sfx("cherry"));
+  }
   let mut new_rings: Vec<(f32, V2F)> = touched_items
-    .map(|&item| {
+    .map(|item| {
       (
         0.0,
         item.pos + V2F::new(ITEM_SIZE as f32 / 2.0, ITEM_SIZE as f32 / 2.0),
@@ -302,21 +602,16 @@ fn update(state: &mut State, key_status: &KeyStatus, game_tick_counter: u32) {
     })
     .collect();
   state.rings.append(&mut new_rings);
-  state.items = state
-    .items
-    .iter()
-    .filter(|&item| {
-      !ball_rect.intersects(&item.rect())
-        && item.pos.y < 50.0
-        && PADDLE_SIZE.x as f32 + 6.0 < item.pos.x
-        && item.pos.x < 78.0
-        && item.age.is_alive()
-    })
-    .map(|x| *x)
-    .collect();
+  state.items.retain(|item| {
+    !ball_rect.intersects(&item.rect())
+      && item.pos.y < 50.0
+      && PADDLE_SIZE.x as f32 + 6.0 < item.pos.x
+      && item.pos.x < 78.0
+      && item.age.is_alive()
+  });
 }
 
-fn render(gcontext: &mut GContext, state: &State) {
+fn render(gcontext: &mut GContext, state: &PlayScene) {
   for (ring_size, ring_pos) in &state.rings {
     let m = (*ring_size / 4.0) as i32 + 1;
     gcontext.draw_circle(
@@ -328,23 +623,35 @@ fn render(gcontext: &mut GContext, state: &State) {
     );
   }
   for item in &state.items {
-    let item_name = match item.item_type {
-      ItemType::Cherry => "cherry",
-      ItemType::Coin => "coin",
-    };
-    let should_draw = match item.age {
-      Age::Infinite => true,
-      Age::Finite { remaining } => remaining > COIN_FLASH_THRESHOLD as i32 || remaining % 2 == 0,
-    };
-    if should_draw {
-      gcontext.draw_surface(item.pos.x as i32, item.pos.y as i32, surface(item_name));
+    // items face the way they drift, expiring coins flash inverted
+    let mut options = DrawOptions::new();
+    if item.velocity.x < 0.0 {
+      options = options.flip_x();
+    }
+    if let Age::Finite { remaining } = item.age {
+      if remaining <= COIN_FLASH_THRESHOLD as i32 && remaining % 2 != 0 {
+        options = options.swap_both(DARK_COLOR, BRIGHT_COLOR);
+      }
     }
+    gcontext.draw_animation_with(
+      item.pos.x as i32,
+      item.pos.y as i32,
+      &item.animation,
+      &options,
+    );
+  }
+  match &state.ball_squash {
+    Some(ball_squash) => gcontext.draw_animation(
+      state.ball_pos.x as i32,
+      state.ball_pos.y as i32,
+      ball_squash,
+    ),
+    None => gcontext.draw_surface(
+      state.ball_pos.x as i32,
+      state.ball_pos.y as i32,
+      state.art.ball,
+    ),
   }
-  gcontext.draw_surface(
-    state.ball_pos.x as i32,
-    state.ball_pos.y as i32,
-    surface("ball"),
-  );
   gcontext.draw_rect(
     state.paddle_pos.x as i32,
     state.paddle_pos.y as i32,
@@ -355,34 +662,124 @@ fn render(gcontext: &mut GContext, state: &State) {
   gcontext.draw_text(84 - 5 * 3, 1, &state.score.to_string());
 }
 
-fn handle_event(state: &mut State, event: &sdl2::event::Event) {
-  match *event {
-    Event::KeyDown {
-      keycode: Some(keycode),
-      ..
-    } => handle_keypress(state, keycode),
-    _ => (),
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use game_lib::golden::*;
+
+  fn golden_path(name: &str) -> String {
+    format!("{}/golden/{}.png", env!("CARGO_MANIFEST_DIR"), name)
   }
-}
 
-fn handle_keypress(state: &mut State, keycode: sdl2::keyboard::Keycode) {
-  if state.control_scheme == ControlScheme::Toggle {
-    match keycode {
-      sdl2::keyboard::Keycode::W => {
-        if state.paddle_dir >= 0.0 {
-          state.paddle_dir = -PADDLE_SPEED as f32;
-        } else {
-          state.paddle_dir = 0.0;
-        }
+  fn playing_state(gcontext: &mut GContext) -> Result<PlayScene, GameError> {
+    let art = init(gcontext)?;
+    let mut state = PlayScene::new(ControlScheme::Toggle, art, &mut GameRng::new(0));
+    state.paddle_pos = P2F::new(1.0, 12.0);
+    state.ball_pos = P2F::new(30.0, 20.0);
+    state.items = vec![
+      Item::new(
+        art,
+        ItemType::Cherry,
+        V2F::new(0.0, 0.0),
+        V2F::new(50.0, 8.0),
+        Age::Infinite,
+      ),
+      Item::new(
+        art,
+        ItemType::Coin,
+        V2F::new(0.0, 0.0),
+        V2F::new(62.0, 30.0),
+        Age::Finite {
+          remaining: COIN_LIFETIME as i32,
+        },
+      ),
+    ];
+    Ok(state)
+  }
+
+  #[test]
+  fn title_screen() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      Ok(TitleScene {
+        control_scheme: ControlScheme::Toggle,
+        art: init(gcontext)?,
+      })
+    });
+    assert_golden(&frame, golden_path("title"));
+  }
+
+  #[test]
+  fn in_play() {
+    let frame = render_frame_with(cell_phone_config(1), playing_state);
+    assert_golden(&frame, golden_path("in_play"));
+  }
+
+  #[test]
+  fn score() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      let mut state = playing_state(gcontext)?;
+      state.score = 127;
+      state.rings = vec![(3.0, V2F::new(40.0, 24.0)), (7.0, V2F::new(20.0, 30.0))];
+      Ok(state)
+    });
+    assert_golden(&frame, golden_path("score"));
+  }
+
+  #[test]
+  fn animated() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      let mut state = playing_state(gcontext)?;
+      for _ in 0..5 {
+        state.items[1].animation.tick();
       }
-      sdl2::keyboard::Keycode::S => {
-        if state.paddle_dir <= 0.0 {
-          state.paddle_dir = PADDLE_SPEED as f32;
-        } else {
-          state.paddle_dir = 0.0;
-        }
+      state.ball_squash = squash(state.art.ball_squash_x);
+      Ok(state)
+    });
+    assert_golden(&frame, golden_path("animated"));
+  }
+
+  #[test]
+  fn expiring_coin() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      let mut state = playing_state(gcontext)?;
+      state.items[0].velocity = V2F::new(-0.5, 0.5);
+      state.items[1].age = Age::Finite { remaining: 5 };
+      Ok(state)
+    });
+    assert_golden(&frame, golden_path("expiring_coin"));
+  }
+
+  #[test]
+  fn game_over() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      Ok(GameOverScene::new(
+        ControlScheme::Toggle,
+        init(gcontext)?,
+        42,
+      ))
+    });
+    assert_golden(&frame, golden_path("game_over"));
+  }
+
+  #[test]
+  fn game_over_title_selected() {
+    let frame = render_frame_with(cell_phone_config(1), |gcontext| {
+      let mut scene = GameOverScene::new(ControlScheme::Toggle, init(gcontext)?, 42);
+      scene.selected = 1;
+      Ok(scene)
+    });
+    assert_golden(&frame, golden_path("game_over_title_selected"));
+  }
+
+  #[test]
+  fn embedded_assets_match_the_source_tree() {
+    let embedded = AssetPack::from_tar(EMBEDDED_ASSETS).unwrap();
+    let dir = AssetPack::from_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets")).unwrap();
+    assert_eq!(embedded.manifest(), dir.manifest());
+    for entry in dir.manifest().unwrap() {
+      if let ManifestEntry::Surface { file, .. } | ManifestEntry::Sfx { file, .. } = entry {
+        assert_eq!(embedded.file(&file), dir.file(&file));
       }
-      _ => {}
     }
   }
 }
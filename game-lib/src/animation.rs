@@ -0,0 +1,192 @@
+// frame animations
+//
+// An animation is a sequence of sprites or surfaces, each shown for a number of ticks. It is
+// plain data, so game state can build one wherever it needs it and hold an `AnimationPlayer`
+// that is ticked in `update` and drawn with `Renderer::draw_animation`.
+use crate::assets::*;
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
+pub enum FrameImage {
+  Sprite(SpriteId),
+  Surface(SurfaceId),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PlayMode {
+  Loop,
+  // forward, then backward, without showing the first and last frame twice
+  PingPong,
+  // stops on the last frame
+  Once,
+}
+
+#[derive(Clone)]
+pub struct Animation {
+  frames: Vec<(FrameImage, u32)>,
+  mode: PlayMode,
+}
+
+impl Animation {
+  pub fn new(mode: PlayMode) -> Animation {
+    Animation {
+      frames: Vec::new(),
+      mode,
+    }
+  }
+
+  pub fn sprite(self, sprite_id: SpriteId, ticks: u32) -> Animation {
+    self.frame(FrameImage::Sprite(sprite_id), ticks)
+  }
+
+  pub fn surface(self, surface_id: SurfaceId, ticks: u32) -> Animation {
+    self.frame(FrameImage::Surface(surface_id), ticks)
+  }
+
+  // frames are shown for at least a tick
+  pub fn frame(mut self, image: FrameImage, ticks: u32) -> Animation {
+    self.frames.push((image, ticks.max(1)));
+    self
+  }
+
+  pub fn mode(&self) -> PlayMode {
+    self.mode
+  }
+
+  // ticks until a ping-pong animation is back at the start
+  pub fn pass_ticks(&self) -> u32 {
+    self
+      .pass_frames()
+      .map(|frame_index| self.frames[frame_index].1)
+      .sum()
+  }
+
+  // the frame indices in the order they are shown during one pass
+  fn pass_frames(&self) -> impl Iterator<Item = usize> {
+    let count = self.frames.len();
+    let back = match self.mode {
+      PlayMode::PingPong if count > 2 => count - 2,
+      _ => 0,
+    };
+    (0..count).chain((1..=back).rev())
+  }
+}
+
+#[derive(Clone)]
+pub struct AnimationPlayer {
+  animation: Animation,
+  elapsed: u32,
+  finished: bool,
+}
+
+impl AnimationPlayer {
+  pub fn new(animation: Animation) -> AnimationPlayer {
+    AnimationPlayer {
+      animation,
+      elapsed: 0,
+      finished: false,
+    }
+  }
+
+  // advances by a tick, returns true when a pass over the animation was just completed
+  pub fn tick(&mut self) -> bool {
+    let pass_ticks = self.animation.pass_ticks();
+    if self.finished || pass_ticks == 0 {
+      return false;
+    }
+    self.elapsed += 1;
+    if self.elapsed < pass_ticks {
+      return false;
+    }
+    if self.animation.mode == PlayMode::Once {
+      self.finished = true;
+      self.elapsed = pass_ticks - 1;
+    } else {
+      self.elapsed = 0;
+    }
+    true
+  }
+
+  // like `tick`, calls `on_complete` when a pass was just completed
+  pub fn tick_then<F: FnOnce()>(&mut self, on_complete: F) {
+    if self.tick() {
+      on_complete();
+    }
+  }
+
+  pub fn restart(&mut self) {
+    self.elapsed = 0;
+    self.finished = false;
+  }
+
+  // only `Once` animations finish
+  pub fn is_finished(&self) -> bool {
+    self.finished
+  }
+
+  pub fn frame_index(&self) -> usize {
+    let mut elapsed = self.elapsed;
+    for frame_index in self.animation.pass_frames() {
+      let ticks = self.animation.frames[frame_index].1;
+      if elapsed < ticks {
+        return frame_index;
+      }
+      elapsed -= ticks;
+    }
+    0
+  }
+
+  // None for animations without frames
+  pub fn frame(&self) -> Option<&FrameImage> {
+    self
+      .animation
+      .frames
+      .get(self.frame_index())
+      .map(|(image, _)| image)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::store::*;
+
+  fn frames(mode: PlayMode, ticks: u32) -> Vec<usize> {
+    let animation = Animation::new(mode)
+      .surface(Handle::new(0), 2)
+      .surface(Handle::new(1), 1)
+      .surface(Handle::new(2), 1);
+    let mut player = AnimationPlayer::new(animation);
+    (0..ticks)
+      .map(|_| {
+        let frame_index = player.frame_index();
+        player.tick();
+        frame_index
+      })
+      .collect()
+  }
+
+  #[test]
+  fn plays_modes() {
+    assert_eq!(frames(PlayMode::Loop, 6), vec![0, 0, 1, 2, 0, 0]);
+    assert_eq!(frames(PlayMode::PingPong, 7), vec![0, 0, 1, 2, 1, 0, 0]);
+    assert_eq!(frames(PlayMode::Once, 6), vec![0, 0, 1, 2, 2, 2]);
+  }
+
+  #[test]
+  fn reports_completion() {
+    let animation = Animation::new(PlayMode::Once)
+      .surface(Handle::new(0), 2)
+      .surface(Handle::new(1), 1);
+    let mut player = AnimationPlayer::new(animation);
+    let mut completions = 0;
+    for _ in 0..5 {
+      player.tick_then(|| completions += 1);
+    }
+    assert_eq!(completions, 1);
+    assert!(player.is_finished());
+
+    player.restart();
+    assert_eq!(player.frame_index(), 0);
+    assert!(!player.is_finished());
+  }
+}
@@ -1,3 +1,4 @@
+#[derive(Clone)]
 pub struct Arr2d<T> {
   width: u32,
   height: u32,
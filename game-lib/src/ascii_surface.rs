@@ -0,0 +1,137 @@
+// surfaces drawn as text
+//
+// A surface file starts with its palette, a line per character like `# = 43523d` with the
+// color in hex, optionally with alpha. A blank line ends the palette and the rows follow, all
+// equally wide. Characters missing from the palette are transparent, as trailing spaces tend
+// to get stripped by editors it is better to use something like `.` for those.
+use crate::color::*;
+use crate::image::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum AsciiSurfaceError {
+  NoRows,
+  // line numbers start at 1
+  InvalidPaletteLine(usize, String),
+  UnevenRow {
+    line: usize,
+    width: usize,
+    expected: usize,
+  },
+}
+
+impl std::fmt::Display for AsciiSurfaceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      AsciiSurfaceError::NoRows => write!(f, "no rows"),
+      AsciiSurfaceError::InvalidPaletteLine(line, text) => {
+        write!(f, "invalid palette entry on line {}: {}", line, text)
+      }
+      AsciiSurfaceError::UnevenRow {
+        line,
+        width,
+        expected,
+      } => write!(
+        f,
+        "row on line {} is {} wide, the first row is {} wide",
+        line, width, expected
+      ),
+    }
+  }
+}
+
+impl std::error::Error for AsciiSurfaceError {}
+
+pub fn parse_ascii_surface(text: &str) -> Result<Image, AsciiSurfaceError> {
+  let lines: Vec<&str> = text.lines().collect();
+  let palette_lines = lines
+    .iter()
+    .position(|line| line.trim().is_empty())
+    .unwrap_or(lines.len());
+  let mut palette = Palette::new();
+  for (ix, line) in lines[..palette_lines].iter().enumerate() {
+    let entry = parse_palette_entry(line)
+      .ok_or_else(|| AsciiSurfaceError::InvalidPaletteLine(ix + 1, line.to_string()))?;
+    palette.insert(entry.0, entry.1);
+  }
+
+  // rows start after the blank line, blank lines at the end are ignored
+  let first_row = (palette_lines + 1).min(lines.len());
+  let mut rows = &lines[first_row..];
+  while let Some((last, rest)) = rows.split_last() {
+    if !last.trim().is_empty() {
+      break;
+    }
+    rows = rest;
+  }
+  Image::try_from_strvec(&palette, rows).map_err(|e| match e {
+    AsciiSurfaceError::UnevenRow {
+      line,
+      width,
+      expected,
+    } => AsciiSurfaceError::UnevenRow {
+      line: line + first_row,
+      width,
+      expected,
+    },
+    e => e,
+  })
+}
+
+// `c = rrggbb` or `c = rrggbbaa`, the color may start with '#'
+fn parse_palette_entry(line: &str) -> Option<(char, Color)> {
+  let mut chars = line.chars();
+  let ch = chars.next()?;
+  let color = chars.as_str().trim().strip_prefix('=')?.trim();
+  let color = color.strip_prefix('#').unwrap_or(color);
+  let component = |ix: usize| {
+    color
+      .get(ix * 2..ix * 2 + 2)
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+  };
+  let color = match color.len() {
+    6 => Color::RGB(component(0)?, component(1)?, component(2)?),
+    8 => Color::RGBA(component(0)?, component(1)?, component(2)?, component(3)?),
+    _ => return None,
+  };
+  Some((ch, color))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::*;
+
+  #[test]
+  fn parses_surfaces() {
+    let text = "# = 43523d\n_ = #C7F0D880\n\n.#_\n#..\n\n";
+    let image = parse_ascii_surface(text).unwrap();
+    assert_eq!(image.size(), crate::types::V2U::new(3, 2));
+    assert_eq!(image.get(0, 0), Some(TRANSPARENT));
+    assert_eq!(image.get(1, 0), Some(Color::RGB(67, 82, 61)));
+    assert_eq!(image.get(2, 0), Some(Color::RGBA(199, 240, 216, 128)));
+    assert_eq!(image.get(0, 1), Some(Color::RGB(67, 82, 61)));
+  }
+
+  #[test]
+  fn reports_errors() {
+    assert_eq!(
+      parse_ascii_surface("# = 43523d\n\n##\n#\n").err(),
+      Some(AsciiSurfaceError::UnevenRow {
+        line: 4,
+        width: 1,
+        expected: 2
+      })
+    );
+    assert_eq!(
+      parse_ascii_surface("# = 43523\n\n#\n").err(),
+      Some(AsciiSurfaceError::InvalidPaletteLine(
+        1,
+        "# = 43523".to_string()
+      ))
+    );
+    assert_eq!(
+      parse_ascii_surface("# = 43523d\n\n").err(),
+      Some(AsciiSurfaceError::NoRows)
+    );
+  }
+}
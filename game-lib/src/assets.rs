@@ -0,0 +1,486 @@
+use crate::animation::*;
+use crate::ascii_surface::*;
+use crate::color::*;
+use crate::error::*;
+use crate::font::*;
+use crate::image::*;
+use crate::pack::*;
+use crate::sheet_metadata::*;
+use crate::store::*;
+use crate::transform::*;
+use crate::types::*;
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct SurfaceName(pub String);
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct SpriteSheetName(pub String);
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct SpriteName(pub String);
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct FontName(pub String);
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct AnimationName(pub String);
+
+impl AssetName for SurfaceName {
+  const KIND: &'static str = "surface";
+  fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl AssetName for SpriteSheetName {
+  const KIND: &'static str = "sprite sheet";
+  fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl AssetName for SpriteName {
+  const KIND: &'static str = "sprite";
+  fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl AssetName for FontName {
+  const KIND: &'static str = "font";
+  fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+impl AssetName for AnimationName {
+  const KIND: &'static str = "animation";
+  fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+pub type SurfaceId = Handle<SurfaceName>;
+pub type SpriteSheetId = Handle<SpriteSheetName>;
+pub type SpriteId = Handle<SpriteName>;
+pub type FontId = Handle<FontName>;
+pub type AnimationId = Handle<AnimationName>;
+
+pub fn surface(name: &str) -> SurfaceName {
+  SurfaceName(name.to_string())
+}
+
+pub fn sprite_sheet(name: &str) -> SpriteSheetName {
+  SpriteSheetName(name.to_string())
+}
+
+pub fn sprite(name: &str) -> SpriteName {
+  SpriteName(name.to_string())
+}
+
+pub fn font(name: &str) -> FontName {
+  FontName(name.to_string())
+}
+
+pub fn animation(name: &str) -> AnimationName {
+  AnimationName(name.to_string())
+}
+
+struct SheetData {
+  surface_id: SurfaceId,
+  // in cells, sheets loaded with metadata are a single cell
+  size: V2U,
+}
+
+enum SpriteData {
+  // a cell of the sheet's grid
+  Cell {
+    sheet_id: SpriteSheetId,
+    sheet_coords: V2U,
+  },
+  // in pixels
+  Rect {
+    sheet_id: SpriteSheetId,
+    pos: P2U,
+    size: V2U,
+  },
+}
+
+// a surface loaded from an ascii surface file, reloaded when the file changes
+struct SurfaceFile {
+  surface_id: SurfaceId,
+  path: PathBuf,
+  modified: Option<SystemTime>,
+}
+
+pub struct Assets {
+  default_font: Font,
+  font_store: Store<FontName, Font>,
+  surface_store: Store<SurfaceName, Image>,
+  surface_files: Vec<SurfaceFile>,
+  sprite_sheet_store: Store<SpriteSheetName, SheetData>,
+  sprite_store: Store<SpriteName, SpriteData>,
+  animation_store: Store<AnimationName, Animation>,
+  // transformed images by source and options, filled while drawing
+  transform_cache: RefCell<HashMap<(FrameImage, DrawOptions), Image>>,
+}
+
+impl Assets {
+  pub fn new(font_color: Color) -> Assets {
+    Assets {
+      default_font: Font::builtin(font_color),
+      font_store: Store::new(),
+      surface_store: Store::new(),
+      surface_files: Vec::new(),
+      sprite_sheet_store: Store::new(),
+      sprite_store: Store::new(),
+      animation_store: Store::new(),
+      transform_cache: RefCell::new(HashMap::new()),
+    }
+  }
+
+  pub fn add_surface(
+    &mut self,
+    surface_name: SurfaceName,
+    palette: &Palette,
+    data: Vec<&str>,
+  ) -> SurfaceId {
+    self.transform_cache.get_mut().clear();
+    self
+      .surface_store
+      .insert(surface_name, Image::from_strvec(palette, &data))
+  }
+
+  // see `parse_ascii_surface` for the format
+  pub fn add_surface_file<P: AsRef<Path>>(
+    &mut self,
+    surface_name: SurfaceName,
+    path: P,
+  ) -> Result<SurfaceId, GameError> {
+    let path = path.as_ref();
+    let modified = modified_time(path);
+    let image = read_surface_file(path)?;
+    let surface_id = self.surface_store.insert(surface_name, image);
+    self.watch_surface_file(surface_id, path, modified);
+    Ok(surface_id)
+  }
+
+  // surfaces from packs read from a directory are reloaded like `add_surface_file`'s
+  pub fn add_surface_from_pack(
+    &mut self,
+    pack: &AssetPack,
+    surface_name: SurfaceName,
+    file: &str,
+  ) -> Result<SurfaceId, PackError> {
+    let path = pack.path(file);
+    let modified = path.as_deref().and_then(modified_time);
+    let image = parse_ascii_surface(pack.text(file)?)
+      .map_err(|e| PackError::InvalidFile(file.to_string(), e.to_string()))?;
+    let surface_id = self.surface_store.insert(surface_name, image);
+    match path {
+      Some(path) => self.watch_surface_file(surface_id, &path, modified),
+      None => self.transform_cache.get_mut().clear(),
+    }
+    Ok(surface_id)
+  }
+
+  fn watch_surface_file(
+    &mut self,
+    surface_id: SurfaceId,
+    path: &Path,
+    modified: Option<SystemTime>,
+  ) {
+    self.transform_cache.get_mut().clear();
+    self
+      .surface_files
+      .retain(|file| file.surface_id != surface_id);
+    self.surface_files.push(SurfaceFile {
+      surface_id,
+      path: path.to_path_buf(),
+      modified,
+    });
+  }
+
+  // Reloads the surfaces whose files changed since they were loaded, for editing art while
+  // the game runs. A file that fails to load is reported on stderr and its surface is kept
+  // as it was, until the file changes again.
+  pub fn reload_changed_surfaces(&mut self) -> Vec<SurfaceId> {
+    let mut reloaded = Vec::new();
+    for file in &mut self.surface_files {
+      let modified = modified_time(&file.path);
+      if modified == file.modified {
+        continue;
+      }
+      file.modified = modified;
+      match read_surface_file(&file.path) {
+        Ok(image) => {
+          *self.surface_store.get_mut(file.surface_id) = image;
+          reloaded.push(file.surface_id);
+        }
+        Err(e) => log_error(e),
+      }
+    }
+    if !reloaded.is_empty() {
+      self.transform_cache.get_mut().clear();
+    }
+    reloaded
+  }
+
+  pub fn add_sprite_sheet(
+    &mut self,
+    sprite_sheet_name: SpriteSheetName,
+    sprite_sheet_path: &str,
+    size: V2U,
+  ) -> Result<SpriteSheetId, GameError> {
+    let path = Path::new(sprite_sheet_path);
+    let data = std::fs::read(path).map_err(|e| GameError::io(path, e))?;
+    let image = decode_surface(&data).map_err(|e| GameError::invalid_file(path, e))?;
+    Ok(self.add_sheet_image(sprite_sheet_name, image, size))
+  }
+
+  // any image format SDL_image reads
+  pub fn add_sprite_sheet_from_pack(
+    &mut self,
+    pack: &AssetPack,
+    sprite_sheet_name: SpriteSheetName,
+    file: &str,
+    size: V2U,
+  ) -> Result<SpriteSheetId, PackError> {
+    let image =
+      decode_surface(pack.file(file)?).map_err(|e| PackError::InvalidFile(file.to_string(), e))?;
+    Ok(self.add_sheet_image(sprite_sheet_name, image, size))
+  }
+
+  fn add_sheet_image(
+    &mut self,
+    sprite_sheet_name: SpriteSheetName,
+    image: Image,
+    size: V2U,
+  ) -> SpriteSheetId {
+    let surface_id = self
+      .surface_store
+      .insert(SurfaceName("#".to_owned() + &sprite_sheet_name.0), image);
+    self.transform_cache.get_mut().clear();
+    self
+      .sprite_sheet_store
+      .insert(sprite_sheet_name, SheetData { size, surface_id })
+  }
+
+  pub fn add_sprite(
+    &mut self,
+    sprite_sheet_id: SpriteSheetId,
+    sprite_name: SpriteName,
+    sheet_x: u32,
+    sheet_y: u32,
+  ) -> SpriteId {
+    self.transform_cache.get_mut().clear();
+    self.sprite_store.insert(
+      sprite_name,
+      SpriteData::Cell {
+        sheet_coords: V2U::new(sheet_x, sheet_y),
+        sheet_id: sprite_sheet_id,
+      },
+    )
+  }
+
+  // Loads a sheet exported by Aseprite or TexturePacker with its JSON metadata. Every frame
+  // becomes a sprite named like the frame, and every Aseprite tag an animation named like the
+  // tag. The image is the one named in the metadata, next to the JSON file, or else the JSON
+  // file's path with a png extension.
+  pub fn add_sprite_sheet_json(
+    &mut self,
+    sprite_sheet_name: SpriteSheetName,
+    json_path: &str,
+  ) -> Result<SpriteSheetId, GameError> {
+    let path = Path::new(json_path);
+    let text = std::fs::read_to_string(path).map_err(|e| GameError::io(path, e))?;
+    let metadata = parse_sheet_metadata(&text).map_err(|e| GameError::invalid_file(path, e))?;
+    let image_path = sheet_image_path(json_path, &metadata);
+    let sheet_id = self.add_sprite_sheet(
+      sprite_sheet_name,
+      &image_path.to_string_lossy(),
+      V2U::new(1, 1),
+    )?;
+    self.add_sheet_metadata(sheet_id, &metadata);
+    Ok(sheet_id)
+  }
+
+  // like `add_sprite_sheet_json`, with the image next to the JSON file in the pack
+  pub fn add_sprite_sheet_json_from_pack(
+    &mut self,
+    pack: &AssetPack,
+    sprite_sheet_name: SpriteSheetName,
+    json_file: &str,
+  ) -> Result<SpriteSheetId, PackError> {
+    let metadata = parse_sheet_metadata(pack.text(json_file)?)
+      .map_err(|e| PackError::InvalidFile(json_file.to_string(), e.to_string()))?;
+    let image_file = sheet_image_path(json_file, &metadata);
+    let sheet_id = self.add_sprite_sheet_from_pack(
+      pack,
+      sprite_sheet_name,
+      &image_file.to_string_lossy(),
+      V2U::new(1, 1),
+    )?;
+    self.add_sheet_metadata(sheet_id, &metadata);
+    Ok(sheet_id)
+  }
+
+  // a sprite per frame and an animation per tag
+  fn add_sheet_metadata(&mut self, sheet_id: SpriteSheetId, metadata: &SheetMetadata) {
+    self.transform_cache.get_mut().clear();
+    let frame_ids: Vec<SpriteId> = metadata
+      .frames
+      .iter()
+      .map(|frame| {
+        self.sprite_store.insert(
+          sprite(&frame.name),
+          SpriteData::Rect {
+            sheet_id,
+            pos: frame.pos,
+            size: frame.size,
+          },
+        )
+      })
+      .collect();
+    for tag in &metadata.tags {
+      self.add_animation(
+        animation(&tag.name),
+        metadata.tag_animation(tag, &frame_ids),
+      );
+    }
+  }
+
+  pub fn surface(&self, surface_id: SurfaceId) -> &Image {
+    self.surface_store.get(surface_id)
+  }
+
+  pub fn sprite(&self, sprite_id: SpriteId) -> ImageRegion<'_> {
+    match self.sprite_store.get(sprite_id) {
+      SpriteData::Cell {
+        sheet_id,
+        sheet_coords,
+      } => {
+        let sprite_sheet = self.sprite_sheet_store.get(*sheet_id);
+        let image = self.surface(sprite_sheet.surface_id);
+        let size = V2U::new(
+          image.width() / sprite_sheet.size.x,
+          image.height() / sprite_sheet.size.y,
+        );
+        ImageRegion {
+          image,
+          pos: P2U::new(size.x * sheet_coords.x, size.y * sheet_coords.y),
+          size,
+        }
+      }
+      SpriteData::Rect {
+        sheet_id,
+        pos,
+        size,
+      } => {
+        let sprite_sheet = self.sprite_sheet_store.get(*sheet_id);
+        ImageRegion {
+          image: self.surface(sprite_sheet.surface_id),
+          pos: *pos,
+          size: *size,
+        }
+      }
+    }
+  }
+
+  pub fn add_animation(
+    &mut self,
+    animation_name: AnimationName,
+    animation: Animation,
+  ) -> AnimationId {
+    self.animation_store.insert(animation_name, animation)
+  }
+
+  pub fn animation(&self, animation_id: AnimationId) -> &Animation {
+    self.animation_store.get(animation_id)
+  }
+
+  pub fn frame_image(&self, frame_image: &FrameImage) -> ImageRegion<'_> {
+    match *frame_image {
+      FrameImage::Sprite(sprite_id) => self.sprite(sprite_id),
+      FrameImage::Surface(surface_id) => self.surface(surface_id).region(),
+    }
+  }
+
+  // transforms the image on first use, later draws with the same options reuse the result
+  pub fn transformed(&self, frame_image: &FrameImage, options: &DrawOptions) -> Ref<'_, Image> {
+    let key = (*frame_image, options.clone());
+    if !self.transform_cache.borrow().contains_key(&key) {
+      let image = transform_image(&self.frame_image(frame_image), options);
+      self.transform_cache.borrow_mut().insert(key.clone(), image);
+    }
+    Ref::map(self.transform_cache.borrow(), |cache| &cache[&key])
+  }
+
+  pub fn add_font(&mut self, font_name: FontName, font: Font) -> FontId {
+    self.font_store.insert(font_name, font)
+  }
+
+  pub fn font(&self, font_id: FontId) -> &Font {
+    self.font_store.get(font_id)
+  }
+
+  // the builtin font in the configured font color
+  pub fn default_font(&self) -> &Font {
+    &self.default_font
+  }
+
+  pub fn font_or_default(&self, font_id: Option<FontId>) -> &Font {
+    match font_id {
+      Some(font_id) => self.font(font_id),
+      None => self.default_font(),
+    }
+  }
+
+  // the handles of assets added earlier, e.g. of sprites named in sheet metadata
+  pub fn surface_id(&self, surface_name: &SurfaceName) -> Result<SurfaceId, AssetError> {
+    self.surface_store.handle(surface_name)
+  }
+
+  pub fn sprite_sheet_id(
+    &self,
+    sprite_sheet_name: &SpriteSheetName,
+  ) -> Result<SpriteSheetId, AssetError> {
+    self.sprite_sheet_store.handle(sprite_sheet_name)
+  }
+
+  pub fn sprite_id(&self, sprite_name: &SpriteName) -> Result<SpriteId, AssetError> {
+    self.sprite_store.handle(sprite_name)
+  }
+
+  pub fn font_id(&self, font_name: &FontName) -> Result<FontId, AssetError> {
+    self.font_store.handle(font_name)
+  }
+
+  pub fn animation_id(&self, animation_name: &AnimationName) -> Result<AnimationId, AssetError> {
+    self.animation_store.handle(animation_name)
+  }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+  std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn read_surface_file(path: &Path) -> Result<Image, GameError> {
+  let text = std::fs::read_to_string(path).map_err(|e| GameError::io(path, e))?;
+  parse_ascii_surface(&text).map_err(|e| GameError::invalid_file(path, e))
+}
+
+// the image named in the metadata next to the JSON file, or else the JSON file as a png
+fn sheet_image_path(json_path: &str, metadata: &SheetMetadata) -> PathBuf {
+  let json_path = Path::new(json_path);
+  match &metadata.image {
+    Some(image) => json_path.with_file_name(image),
+    None => json_path.with_extension("png"),
+  }
+}
+
+fn decode_surface(data: &[u8]) -> Result<Image, String> {
+  use sdl2::image::ImageRWops;
+  let surface = sdl2::rwops::RWops::from_bytes(data)?.load()?;
+  Image::try_from_surface(&surface)
+}
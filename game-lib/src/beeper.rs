@@ -0,0 +1,299 @@
+// A one voice square wave synthesizer, like the buzzer of an old phone
+//
+// Sounds are sequences of notes sharing a pulse width, volume and envelope. They are
+// rendered sample by sample, so they can be played on the audio device or rendered into a
+// buffer without one.
+use crate::error::*;
+
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct SfxName(pub String);
+
+pub fn sfx(name: &str) -> SfxName {
+  SfxName(name.to_string())
+}
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+// Applied to every note. The level rises to 1 during the attack, falls to `sustain` during
+// the decay and fades to 0 during the last `release_ms` of the note.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Envelope {
+  pub attack_ms: u32,
+  pub decay_ms: u32,
+  pub sustain: f32,
+  pub release_ms: u32,
+}
+
+impl Envelope {
+  pub const FLAT: Envelope = Envelope {
+    attack_ms: 0,
+    decay_ms: 0,
+    sustain: 1.0,
+    release_ms: 0,
+  };
+
+  // short fades against clicks between notes
+  pub const BEEP: Envelope = Envelope {
+    attack_ms: 2,
+    decay_ms: 0,
+    sustain: 1.0,
+    release_ms: 5,
+  };
+
+  fn level(&self, t_ms: f32, duration_ms: f32) -> f32 {
+    let (attack, decay, release) = (
+      self.attack_ms as f32,
+      self.decay_ms as f32,
+      self.release_ms as f32,
+    );
+    let level = if t_ms < attack {
+      t_ms / attack
+    } else if t_ms < attack + decay {
+      1.0 - (1.0 - self.sustain) * (t_ms - attack) / decay
+    } else {
+      self.sustain
+    };
+    let until_end = duration_ms - t_ms;
+    if until_end < release {
+      level * until_end / release
+    } else {
+      level
+    }
+  }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Note {
+  // in Hz, 0 for a rest
+  pub frequency: f32,
+  pub duration_ms: u32,
+}
+
+impl Note {
+  pub fn new(frequency: f32, duration_ms: u32) -> Note {
+    Note {
+      frequency,
+      duration_ms,
+    }
+  }
+
+  pub fn rest(duration_ms: u32) -> Note {
+    Note::new(0.0, duration_ms)
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sound {
+  pub notes: Vec<Note>,
+  // the part of each period the wave is high, 0.5 is a square wave
+  pub duty: f32,
+  pub volume: f32,
+  pub envelope: Envelope,
+}
+
+impl Sound {
+  pub fn sequence(notes: Vec<Note>) -> Sound {
+    Sound {
+      notes,
+      duty: 0.5,
+      volume: 0.25,
+      envelope: Envelope::BEEP,
+    }
+  }
+
+  pub fn tone(frequency: f32, duration_ms: u32) -> Sound {
+    Sound::sequence(vec![Note::new(frequency, duration_ms)])
+  }
+
+  pub fn duty(self, duty: f32) -> Sound {
+    Sound { duty, ..self }
+  }
+
+  pub fn volume(self, volume: f32) -> Sound {
+    Sound { volume, ..self }
+  }
+
+  pub fn envelope(self, envelope: Envelope) -> Sound {
+    Sound { envelope, ..self }
+  }
+
+  pub fn duration_ms(&self) -> u32 {
+    self.notes.iter().map(|note| note.duration_ms).sum()
+  }
+
+  // the sample at `index`, none after the last note
+  pub fn sample(&self, index: u64, sample_rate: u32) -> Option<f32> {
+    let t_ms = index as f32 * 1000.0 / sample_rate as f32;
+    let mut note_start_ms = 0.0;
+    for note in &self.notes {
+      let duration_ms = note.duration_ms as f32;
+      if t_ms < note_start_ms + duration_ms {
+        if note.frequency <= 0.0 {
+          return Some(0.0);
+        }
+        let t_note_ms = t_ms - note_start_ms;
+        let phase = (t_note_ms * note.frequency / 1000.0).fract();
+        let wave = if phase < self.duty { 1.0 } else { -1.0 };
+        return Some(wave * self.volume * self.envelope.level(t_note_ms, duration_ms));
+      }
+      note_start_ms += duration_ms;
+    }
+    None
+  }
+
+  pub fn render(&self, sample_rate: u32) -> Vec<f32> {
+    (0..)
+      .map(|index| self.sample(index, sample_rate))
+      .take_while(|sample| sample.is_some())
+      .flatten()
+      .collect()
+  }
+
+  pub fn save_wav<P: AsRef<std::path::Path>>(&self, path: P) -> Result<(), GameError> {
+    write_wav(path, &self.render(SAMPLE_RATE), SAMPLE_RATE)
+  }
+}
+
+// 16 bit mono pcm
+pub fn write_wav<P: AsRef<std::path::Path>>(
+  path: P,
+  samples: &[f32],
+  sample_rate: u32,
+) -> Result<(), GameError> {
+  let path = path.as_ref();
+  write_wav_data(path, samples, sample_rate).map_err(|e| GameError::io(path, e))
+}
+
+fn write_wav_data(
+  path: &std::path::Path,
+  samples: &[f32],
+  sample_rate: u32,
+) -> std::io::Result<()> {
+  use std::io::Write;
+  let data_len = samples.len() as u32 * 2;
+  let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+  let mut header = Vec::new();
+  header.extend_from_slice(b"RIFF");
+  header.extend_from_slice(&(36 + data_len).to_le_bytes());
+  header.extend_from_slice(b"WAVEfmt ");
+  header.extend_from_slice(&16u32.to_le_bytes());
+  header.extend_from_slice(&1u16.to_le_bytes()); // pcm
+  header.extend_from_slice(&1u16.to_le_bytes()); // channels
+  header.extend_from_slice(&sample_rate.to_le_bytes());
+  header.extend_from_slice(&(sample_rate * 2).to_le_bytes()); // bytes per second
+  header.extend_from_slice(&2u16.to_le_bytes()); // bytes per frame
+  header.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+  header.extend_from_slice(b"data");
+  header.extend_from_slice(&data_len.to_le_bytes());
+  writer.write_all(&header)?;
+  for sample in samples {
+    let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+    writer.write_all(&value.to_le_bytes())?;
+  }
+  writer.flush()
+}
+
+// Plays one sound at a time, a new sound cuts off the one playing.
+pub struct Beeper {
+  sample_rate: u32,
+  sound: Option<Sound>,
+  position: u64,
+}
+
+impl Beeper {
+  pub fn new(sample_rate: u32) -> Beeper {
+    Beeper {
+      sample_rate,
+      sound: None,
+      position: 0,
+    }
+  }
+
+  pub fn play(&mut self, sound: Sound) {
+    self.sound = Some(sound);
+    self.position = 0;
+  }
+
+  pub fn stop(&mut self) {
+    self.sound = None;
+  }
+
+  pub fn is_playing(&self) -> bool {
+    self.sound.is_some()
+  }
+
+  // silence when nothing is playing
+  pub fn fill(&mut self, out: &mut [f32]) {
+    for sample in out.iter_mut() {
+      *sample = match &self.sound {
+        Some(sound) => match sound.sample(self.position, self.sample_rate) {
+          Some(value) => value,
+          None => {
+            self.sound = None;
+            0.0
+          }
+        },
+        None => 0.0,
+      };
+      self.position += 1;
+    }
+  }
+}
+
+impl sdl2::audio::AudioCallback for Beeper {
+  type Channel = f32;
+
+  fn callback(&mut self, out: &mut [f32]) {
+    self.fill(out);
+  }
+}
+
+// none if there is no audio device
+pub fn open_audio_device(sdl_context: &sdl2::Sdl) -> Option<sdl2::audio::AudioDevice<Beeper>> {
+  let audio_subsystem = sdl_context.audio().ok()?;
+  let desired_spec = sdl2::audio::AudioSpecDesired {
+    freq: Some(SAMPLE_RATE as i32),
+    channels: Some(1),
+    samples: Some(512),
+  };
+  let device = audio_subsystem
+    .open_playback(None, &desired_spec, |spec| Beeper::new(spec.freq as u32))
+    .ok()?;
+  device.resume();
+  Some(device)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn render_length_and_silence() {
+    let sound = Sound::sequence(vec![Note::new(1000.0, 10), Note::rest(10)]);
+    let samples = sound.render(1000);
+    assert_eq!(samples.len(), 20);
+    assert!(samples[10..].iter().all(|&s| s == 0.0));
+  }
+
+  #[test]
+  fn square_wave_duty() {
+    let sound = Sound::tone(100.0, 100)
+      .envelope(Envelope::FLAT)
+      .volume(1.0)
+      .duty(0.25);
+    let samples = sound.render(1000);
+    let high = samples.iter().filter(|&&s| s > 0.0).count();
+    assert_eq!(high, 30);
+    assert!(samples.iter().all(|&s| s == 1.0 || s == -1.0));
+  }
+
+  #[test]
+  fn beeper_stops_after_sound() {
+    let mut beeper = Beeper::new(1000);
+    beeper.play(Sound::tone(500.0, 5));
+    let mut out = [1.0; 8];
+    beeper.fill(&mut out);
+    assert!(!beeper.is_playing());
+    assert!(out[5..].iter().all(|&s| s == 0.0));
+  }
+}
@@ -0,0 +1,70 @@
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Color {
+  pub r: u8,
+  pub g: u8,
+  pub b: u8,
+  pub a: u8,
+}
+
+#[allow(non_snake_case)]
+impl Color {
+  pub const WHITE: Color = Color::RGB(255, 255, 255);
+  pub const BLACK: Color = Color::RGB(0, 0, 0);
+
+  pub const fn RGB(r: u8, g: u8, b: u8) -> Color {
+    Color { r, g, b, a: 255 }
+  }
+
+  pub const fn RGBA(r: u8, g: u8, b: u8, a: u8) -> Color {
+    Color { r, g, b, a }
+  }
+
+  pub fn blend_over(self, dst: Color) -> Color {
+    match self.a {
+      0 => dst,
+      255 => self,
+      a => {
+        let mix = |s: u8, d: u8| ((s as u32 * a as u32 + d as u32 * (255 - a as u32)) / 255) as u8;
+        Color::RGBA(
+          mix(self.r, dst.r),
+          mix(self.g, dst.g),
+          mix(self.b, dst.b),
+          mix(255, dst.a),
+        )
+      }
+    }
+  }
+
+  // `t` goes from 0 (self) to 1 (other)
+  pub fn lerp(self, other: Color, t: f32) -> Color {
+    let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+    Color::RGBA(
+      mix(self.r, other.r),
+      mix(self.g, other.g),
+      mix(self.b, other.b),
+      mix(self.a, other.a),
+    )
+  }
+
+  // `color`, as opaque as self
+  pub fn tint(self, color: Color) -> Color {
+    Color::RGBA(
+      color.r,
+      color.g,
+      color.b,
+      (self.a as u32 * color.a as u32 / 255) as u8,
+    )
+  }
+}
+
+impl From<Color> for sdl2::pixels::Color {
+  fn from(color: Color) -> sdl2::pixels::Color {
+    sdl2::pixels::Color::RGBA(color.r, color.g, color.b, color.a)
+  }
+}
+
+impl From<sdl2::pixels::Color> for Color {
+  fn from(color: sdl2::pixels::Color) -> Color {
+    Color::RGBA(color.r, color.g, color.b, color.a)
+  }
+}
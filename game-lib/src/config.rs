@@ -1,15 +1,36 @@
+use crate::color::*;
 use crate::types::*;
 
 pub struct Config {
   pub scale: u32,
   pub screen_size: V2U,
-  pub font_color: sdl2::pixels::Color,
-  pub background_color: sdl2::pixels::Color,
+  pub font_color: Color,
+  pub background_color: Color,
+  // skip the window and canvas, only render into the software framebuffer
+  pub headless: bool,
+  // show a clickable phone keypad below the lcd
+  pub on_screen_keypad: bool,
 }
 
-pub const TRANSPARENT: sdl2::pixels::Color = sdl2::pixels::Color::RGBA(0, 0, 0, 0);
-pub const DARK_COLOR: sdl2::pixels::Color = sdl2::pixels::Color::RGB(67, 82, 61);
-pub const BRIGHT_COLOR: sdl2::pixels::Color = sdl2::pixels::Color::RGB(199, 240, 216);
+impl Config {
+  pub fn headless(self) -> Config {
+    Config {
+      headless: true,
+      ..self
+    }
+  }
+
+  pub fn on_screen_keypad(self) -> Config {
+    Config {
+      on_screen_keypad: true,
+      ..self
+    }
+  }
+}
+
+pub const TRANSPARENT: Color = Color::RGBA(0, 0, 0, 0);
+pub const DARK_COLOR: Color = Color::RGB(67, 82, 61);
+pub const BRIGHT_COLOR: Color = Color::RGB(199, 240, 216);
 
 pub fn cell_phone_config(scale: u32) -> Config {
   Config {
@@ -17,6 +38,8 @@ pub fn cell_phone_config(scale: u32) -> Config {
     screen_size: V2U::new(84, 48),
     font_color: DARK_COLOR,
     background_color: BRIGHT_COLOR,
+    headless: false,
+    on_screen_keypad: false,
   }
 }
 
@@ -24,8 +47,10 @@ pub fn qqvga_config(scale: u32) -> Config {
   Config {
     scale,
     screen_size: V2U::new(160, 120),
-    font_color: sdl2::pixels::Color::WHITE,
-    background_color: sdl2::pixels::Color::BLACK,
+    font_color: Color::WHITE,
+    background_color: Color::BLACK,
+    headless: false,
+    on_screen_keypad: false,
   }
 }
 
@@ -33,8 +58,10 @@ pub fn qvga_config(scale: u32) -> Config {
   Config {
     scale,
     screen_size: V2U::new(320, 240),
-    font_color: sdl2::pixels::Color::WHITE,
-    background_color: sdl2::pixels::Color::BLACK,
+    font_color: Color::WHITE,
+    background_color: Color::BLACK,
+    headless: false,
+    on_screen_keypad: false,
   }
 }
 
@@ -42,7 +69,9 @@ pub fn vga_config(scale: u32) -> Config {
   Config {
     scale,
     screen_size: V2U::new(640, 480),
-    font_color: sdl2::pixels::Color::WHITE,
-    background_color: sdl2::pixels::Color::BLACK,
+    font_color: Color::WHITE,
+    background_color: Color::BLACK,
+    headless: false,
+    on_screen_keypad: false,
   }
 }
@@ -0,0 +1,76 @@
+// errors surfaced to games
+//
+// Setting up the game and loading files return these, so a binary can print what went wrong
+// instead of crashing. Failures while the game runs, like a frame that can't be drawn, are
+// logged to stderr and the game carries on.
+use crate::pack::*;
+use crate::store::*;
+use std::path::Path;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum GameError {
+  // what was being done, e.g. "create the window", and SDL's error
+  Sdl(&'static str, String),
+  // the path and what went wrong
+  Io(String, String),
+  // a file that failed to parse or decode, and why
+  InvalidFile(String, String),
+  Asset(AssetError),
+  Pack(PackError),
+}
+
+impl GameError {
+  pub(crate) fn io(path: &Path, e: std::io::Error) -> GameError {
+    GameError::Io(path.display().to_string(), e.to_string())
+  }
+
+  pub(crate) fn invalid_file<E: std::fmt::Display>(path: &Path, e: E) -> GameError {
+    GameError::InvalidFile(path.display().to_string(), e.to_string())
+  }
+}
+
+impl std::fmt::Display for GameError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      GameError::Sdl(what, e) => write!(f, "can't {}: {}", what, e),
+      GameError::Io(path, e) => write!(f, "{}: {}", path, e),
+      GameError::InvalidFile(path, e) => write!(f, "{}: {}", path, e),
+      GameError::Asset(e) => write!(f, "{}", e),
+      GameError::Pack(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl std::error::Error for GameError {}
+
+impl From<AssetError> for GameError {
+  fn from(e: AssetError) -> GameError {
+    GameError::Asset(e)
+  }
+}
+
+impl From<PackError> for GameError {
+  fn from(e: PackError) -> GameError {
+    GameError::Pack(e)
+  }
+}
+
+// for failures that shouldn't stop the game
+pub(crate) fn log_error<E: std::fmt::Display>(e: E) {
+  eprintln!("{}", e);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::image::*;
+
+  #[test]
+  fn names_the_failing_file() {
+    let error = Image::load_png("no/such/image.png").err().unwrap();
+    assert!(matches!(&error, GameError::Io(path, _) if path == "no/such/image.png"));
+
+    let error: GameError = PackError::MissingFile("ball.txt".to_string()).into();
+    assert_eq!(error.to_string(), "ball.txt is missing from the asset pack");
+  }
+}
@@ -0,0 +1,538 @@
+// bitmap fonts
+//
+// A font is a set of glyph images with per glyph advance widths, so proportional fonts work.
+// Besides the builtin 4x5 font, fonts can be loaded from BDF files or from png glyph sheets,
+// a grid of equally sized cells plus the characters in them in row-major order.
+use crate::color::*;
+use crate::error::*;
+use crate::image::*;
+use crate::pack::*;
+use crate::renderer::*;
+use crate::types::*;
+use std::collections::HashMap;
+
+// drawn for characters a font has no glyph for, then '?'
+pub const REPLACEMENT_CHAR: char = '\u{fffd}';
+
+#[derive(Clone)]
+pub struct Glyph {
+  pub image: Image,
+  // from the top left of the line to the top left of the image
+  pub offset: V2I,
+  pub advance: u32,
+}
+
+pub struct Font {
+  glyphs: HashMap<char, Glyph>,
+  line_height: u32,
+}
+
+impl Font {
+  pub fn new(line_height: u32) -> Font {
+    Font {
+      glyphs: HashMap::new(),
+      line_height,
+    }
+  }
+
+  // fixed width, uppercase letters are drawn with the lowercase glyphs
+  pub fn builtin(color: Color) -> Font {
+    let sheet = Image::from_strvec(&[('o', color)].iter().cloned().collect(), FONT_DATA);
+    let mut font = Font::new(FONT_HEIGHT);
+    font.add_glyph(' ', Glyph::blank(FONT_WIDTH + 1));
+    let chars = FONT_CHARS.chars().chain(std::iter::once(REPLACEMENT_CHAR));
+    for (ix, ch) in chars.enumerate() {
+      let pos = P2U::new(ix as u32 % 10 * FONT_WIDTH, ix as u32 / 10 * FONT_HEIGHT);
+      font.add_glyph(
+        ch,
+        Glyph {
+          image: copy_region(&sheet, pos, V2U::new(FONT_WIDTH, FONT_HEIGHT)),
+          offset: V2I::new(0, 0),
+          advance: FONT_WIDTH + 1,
+        },
+      );
+    }
+    font
+  }
+
+  // Glyphs are trimmed to their opaque columns and spaced by one pixel, empty cells advance
+  // by half a cell. Fails for empty cells and cells that don't fit the sheet.
+  pub fn from_glyph_sheet(sheet: &Image, cell_size: V2U, chars: &str) -> Result<Font, String> {
+    if cell_size.x == 0
+      || cell_size.y == 0
+      || cell_size.x > sheet.width()
+      || cell_size.y > sheet.height()
+    {
+      return Err(format!(
+        "{}x{} cells don't fit a {}x{} glyph sheet",
+        cell_size.x,
+        cell_size.y,
+        sheet.width(),
+        sheet.height()
+      ));
+    }
+    let columns = sheet.width() / cell_size.x;
+    let mut font = Font::new(cell_size.y);
+    for (ix, ch) in chars.chars().enumerate() {
+      let ix = ix as u32;
+      let pos = P2U::new(ix % columns * cell_size.x, ix / columns * cell_size.y);
+      if pos.y + cell_size.y > sheet.height() {
+        break;
+      }
+      let is_opaque = |x: u32| (0..cell_size.y).any(|y| opaque_at(sheet, pos.x + x, pos.y + y));
+      let glyph = match (
+        (0..cell_size.x).find(|x| is_opaque(*x)),
+        (0..cell_size.x).rev().find(|x| is_opaque(*x)),
+      ) {
+        (Some(left), Some(right)) => Glyph {
+          image: copy_region(
+            sheet,
+            P2U::new(pos.x + left, pos.y),
+            V2U::new(right - left + 1, cell_size.y),
+          ),
+          offset: V2I::new(0, 0),
+          advance: right - left + 2,
+        },
+        _ => Glyph::blank(cell_size.x / 2),
+      };
+      font.add_glyph(ch, glyph);
+    }
+    Ok(font)
+  }
+
+  pub fn load_glyph_sheet<P: AsRef<std::path::Path>>(
+    path: P,
+    cell_size: V2U,
+    chars: &str,
+  ) -> Result<Font, GameError> {
+    let path = path.as_ref();
+    Font::from_glyph_sheet(&Image::load_png(path)?, cell_size, chars)
+      .map_err(|e| GameError::invalid_file(path, e))
+  }
+
+  pub fn load_bdf<P: AsRef<std::path::Path>>(path: P, color: Color) -> Result<Font, GameError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| GameError::io(path, e))?;
+    parse_bdf(&text, color).map_err(|e| GameError::invalid_file(path, e))
+  }
+
+  pub fn glyph_sheet_from_pack(
+    pack: &AssetPack,
+    file: &str,
+    cell_size: V2U,
+    chars: &str,
+  ) -> Result<Font, PackError> {
+    let sheet = Image::decode_png(pack.file(file)?)
+      .map_err(|e| PackError::InvalidFile(file.to_string(), e))?;
+    Font::from_glyph_sheet(&sheet, cell_size, chars)
+      .map_err(|e| PackError::InvalidFile(file.to_string(), e))
+  }
+
+  pub fn bdf_from_pack(pack: &AssetPack, file: &str, color: Color) -> Result<Font, PackError> {
+    parse_bdf(pack.text(file)?, color)
+      .map_err(|e| PackError::InvalidFile(file.to_string(), e.to_string()))
+  }
+
+  pub fn add_glyph(&mut self, ch: char, glyph: Glyph) {
+    self.glyphs.insert(ch, glyph);
+  }
+
+  // falls back to the other case, then to the replacement glyph
+  pub fn glyph(&self, ch: char) -> Option<&Glyph> {
+    [
+      ch,
+      ch.to_ascii_lowercase(),
+      ch.to_ascii_uppercase(),
+      REPLACEMENT_CHAR,
+      '?',
+    ]
+    .iter()
+    .find_map(|ch| self.glyphs.get(ch))
+  }
+
+  pub fn line_height(&self) -> u32 {
+    self.line_height
+  }
+
+  // the sum of the advances, including the spacing after the last glyph
+  pub fn text_width(&self, text: &str) -> u32 {
+    text
+      .chars()
+      .filter_map(|ch| self.glyph(ch))
+      .map(|glyph| glyph.advance)
+      .sum()
+  }
+
+  pub fn draw(&self, target: &mut dyn DrawTarget, x: i32, y: i32, text: &str) {
+    self.draw_glyphs(target, x, y, text, None);
+  }
+
+  // the glyphs are drawn in `color` instead of their own
+  pub fn draw_colored(
+    &self,
+    target: &mut dyn DrawTarget,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: Color,
+  ) {
+    self.draw_glyphs(target, x, y, text, Some(color));
+  }
+
+  // codes not in `palette` switch back to the glyphs' own color
+  pub fn draw_markup(
+    &self,
+    target: &mut dyn DrawTarget,
+    x: i32,
+    y: i32,
+    text: &str,
+    palette: &Palette,
+  ) {
+    let mut pen_x = x;
+    for (code, span) in parse_markup(text) {
+      let color = code.and_then(|code| palette.get(&code).copied());
+      pen_x = self.draw_glyphs(target, pen_x, y, &span, color);
+    }
+  }
+
+  pub fn markup_width(&self, text: &str) -> u32 {
+    parse_markup(text)
+      .iter()
+      .map(|(_, span)| self.text_width(span))
+      .sum()
+  }
+
+  // returns where the next glyph would go
+  fn draw_glyphs(
+    &self,
+    target: &mut dyn DrawTarget,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: Option<Color>,
+  ) -> i32 {
+    let mut pen_x = x;
+    for glyph in text.chars().filter_map(|ch| self.glyph(ch)) {
+      let (glyph_x, glyph_y) = (pen_x + glyph.offset.x, y + glyph.offset.y);
+      match color {
+        Some(color) => target.blit_colored(&glyph.image.region(), glyph_x, glyph_y, color),
+        None => target.blit(&glyph.image.region(), glyph_x, glyph_y),
+      }
+      pen_x += glyph.advance as i32;
+    }
+    pen_x
+  }
+}
+
+// splits text with color codes into spans and the code they are drawn with: `^c` switches to
+// palette color `c`, `^.` back to the font's color and `^^` is a caret
+pub fn parse_markup(text: &str) -> Vec<(Option<char>, String)> {
+  let mut spans = vec![(None, String::new())];
+  let mut chars = text.chars();
+  while let Some(ch) = chars.next() {
+    if ch != '^' {
+      spans.last_mut().unwrap().1.push(ch);
+      continue;
+    }
+    match chars.next() {
+      Some('^') | None => spans.last_mut().unwrap().1.push('^'),
+      Some('.') => spans.push((None, String::new())),
+      Some(code) => spans.push((Some(code), String::new())),
+    }
+  }
+  spans.retain(|(_, span)| !span.is_empty());
+  spans
+}
+
+impl Glyph {
+  pub fn blank(advance: u32) -> Glyph {
+    Glyph {
+      image: Image::new(0, 0),
+      offset: V2I::new(0, 0),
+      advance,
+    }
+  }
+}
+
+fn copy_region(image: &Image, pos: P2U, size: V2U) -> Image {
+  let mut copy = Image::new(size.x, size.y);
+  copy.blit(&ImageRegion { image, pos, size }, 0, 0);
+  copy
+}
+
+fn opaque_at(image: &Image, x: u32, y: u32) -> bool {
+  image.get(x as i32, y as i32).is_some_and(|c| c.a > 0)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BdfError {
+  // neither FONTBOUNDINGBOX nor FONT_ASCENT and FONT_DESCENT before the first glyph
+  MissingBoundingBox,
+  // the line number and the line
+  InvalidLine(usize, String),
+}
+
+impl std::fmt::Display for BdfError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      BdfError::MissingBoundingBox => write!(f, "missing FONTBOUNDINGBOX"),
+      BdfError::InvalidLine(line_number, line) => {
+        write!(f, "invalid line {} '{}'", line_number, line)
+      }
+    }
+  }
+}
+
+impl std::error::Error for BdfError {}
+
+// glyphs are placed on a common baseline, FONT_ASCENT below the top of the line
+pub fn parse_bdf(text: &str, color: Color) -> Result<Font, BdfError> {
+  let mut bounding_box: Option<[i32; 4]> = None;
+  let (mut ascent, mut descent, mut default_char) = (None, None, None);
+  let mut glyphs: Vec<(Option<char>, Glyph)> = Vec::new();
+  let (mut encoding, mut advance, mut bbx) = (None, 0, [0; 4]);
+
+  let mut lines = text.lines().enumerate();
+  while let Some((ix, line)) = lines.next() {
+    let invalid = || BdfError::InvalidLine(ix + 1, line.to_string());
+    let mut words = line.split_whitespace();
+    let keyword = words.next().unwrap_or("");
+    let numbers = words
+      .map(|w| w.parse::<i32>())
+      .collect::<Result<Vec<_>, _>>();
+    let number = |at: usize| -> Result<i32, BdfError> {
+      match &numbers {
+        Ok(numbers) => numbers.get(at).copied().ok_or_else(invalid),
+        Err(_) => Err(invalid()),
+      }
+    };
+    match keyword {
+      "FONTBOUNDINGBOX" => bounding_box = Some([number(0)?, number(1)?, number(2)?, number(3)?]),
+      "FONT_ASCENT" => ascent = Some(number(0)?),
+      "FONT_DESCENT" => descent = Some(number(0)?),
+      "DEFAULT_CHAR" => default_char = Some(number(0)?),
+      "STARTCHAR" => {
+        encoding = None;
+        advance = 0;
+        bbx = [0; 4];
+      }
+      // -1 marks glyphs without a standard encoding
+      "ENCODING" => encoding = std::char::from_u32(number(0)? as u32),
+      "DWIDTH" => advance = number(0)?,
+      "BBX" => bbx = [number(0)?, number(1)?, number(2)?, number(3)?],
+      "BITMAP" => {
+        let [width, height, x_offset, y_offset] = bbx;
+        let ascent = match (ascent, bounding_box) {
+          (Some(ascent), _) => ascent,
+          (None, Some([_, h, _, y])) => h + y,
+          (None, None) => return Err(BdfError::MissingBoundingBox),
+        };
+        let mut image = Image::new(width.max(0) as u32, height.max(0) as u32);
+        for y in 0..height {
+          let (ix, row) = lines.next().ok_or_else(invalid)?;
+          let invalid_row = || BdfError::InvalidLine(ix + 1, row.to_string());
+          let row = row.trim();
+          for x in 0..width {
+            let digit = row
+              .get(x as usize / 4..x as usize / 4 + 1)
+              .and_then(|d| u8::from_str_radix(d, 16).ok())
+              .ok_or_else(invalid_row)?;
+            if digit & (8 >> (x % 4)) != 0 {
+              image.set_pixel(x, y, color);
+            }
+          }
+        }
+        glyphs.push((
+          encoding,
+          Glyph {
+            image,
+            offset: V2I::new(x_offset, ascent - height - y_offset),
+            advance: advance.max(0) as u32,
+          },
+        ));
+      }
+      _ => {}
+    }
+  }
+
+  let (ascent, descent) = match (ascent, descent, bounding_box) {
+    (Some(ascent), Some(descent), _) => (ascent, descent),
+    (_, _, Some([_, h, _, y])) => (ascent.unwrap_or(h + y), descent.unwrap_or(-y)),
+    _ => return Err(BdfError::MissingBoundingBox),
+  };
+  let mut font = Font::new((ascent + descent).max(0) as u32);
+  let default_char = default_char.and_then(|code| std::char::from_u32(code as u32));
+  for (ch, glyph) in glyphs {
+    if let Some(ch) = ch {
+      if Some(ch) == default_char && !font.glyphs.contains_key(&REPLACEMENT_CHAR) {
+        font.add_glyph(REPLACEMENT_CHAR, glyph.clone());
+      }
+      font.add_glyph(ch, glyph);
+    }
+  }
+  Ok(font)
+}
+
+pub const FONT_WIDTH: u32 = 4;
+pub const FONT_HEIGHT: u32 = 5;
+
+// the glyphs in FONT_DATA, followed by the replacement glyph
+const FONT_CHARS: &str = "0123456789abcdefghijklmnopqrstuvwxyz.,:;!?-+/()'\"%#$&*<=>@[\\]^_`{|}~";
+
+#[rustfmt::skip]
+const FONT_DATA: &[&str] = &[
+// L   L   L   L   L   L   L   L   L   L   L
+  " oo    o oo  oo o   oooo ooooooo oo  oo ",
+  "o  o   oo  oo  oo   o   o      oo  oo  o",
+  "o  o   o  o   o o o  oo ooo   o  oo  ooo",
+  "o  o   o o  o  ooooo   oo  o o  o  o   o",
+  " oo    ooooo oo   o ooo  oo o    oo  oo ",
+
+  " oo ooo  oo ooo oooooooo oo o  o  o    o",
+  "o  oo  oo  oo  oo   o   o  oo  o  o    o",
+  "ooooooo o   o  oooo ooo o   oooo  o    o",
+  "o  oo  oo  oo  oo   o   o ooo  o  o o  o",
+  "o  oooo  oo ooo ooooo    oo o  o  o  oo ",
+
+  "o  oo   o  oo  o oo ooo  oo ooo  ooooooo",
+  "o o o   oooooo oo  oo  oo  oo  oo     o ",
+  "oo  o   oo oo ooo  oooo o  oooo  oo   o ",
+  "o o o   o  oo  oo  oo   o ooo  o   o  o ",
+  "o  oooooo  oo  o oo o    oooo  oooo   o ",
+
+  "o  oo  oo  oo  oo  ooooo                ",
+  "o  oo  oo  oo  oo  o   o         o   o  ",
+  "o  oo  oo oo oo  oo  oo                 ",
+  "o  o o o ooo oo   o o        o   o   o  ",
+  " oo   o  oo o  o  o oooo o  o       o   ",
+
+  " o  ooo            o  o  o   o  o o o  o",
+  " o     o     o    o  o    o  o  o o   o ",
+  " o   oo ooo ooo   o  o    o           o ",
+  "             o   o   o    o          o  ",
+  " o   o          o     o  o          o  o",
+
+  " o o ooo o        o     o    oo  oo o   ",
+  "oooooo  o o o o  o  ooo  o  o  o o   o  ",
+  " o o oo  o o o  o         o o oo o   o  ",
+  "oooo  ooo o o o  o  ooo  o  o    o    o ",
+  " o oooo  o o      o     o    ooo oo    o",
+
+  " oo  o      o     oo o  oo      oooo    ",
+  "  o o o      o   o   o    o  o oo  o    ",
+  "  o             oo   o    ooo o o  o    ",
+  "  o              o   o    o     o  o    ",
+  " oo     oooo      oo o  oo      oooo    ",
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BDF: &str = "STARTFONT 2.1
+FONTBOUNDINGBOX 3 4 0 -1
+STARTPROPERTIES 2
+FONT_ASCENT 3
+FONT_DESCENT 1
+ENDPROPERTIES
+CHARS 2
+STARTCHAR i
+ENCODING 105
+DWIDTH 2 0
+BBX 1 3 0 0
+BITMAP
+80
+00
+80
+ENDCHAR
+STARTCHAR y
+ENCODING 121
+DWIDTH 4 0
+BBX 3 3 0 -1
+BITMAP
+A0
+40
+80
+ENDCHAR
+ENDFONT
+";
+
+  #[test]
+  fn parses_bdf() {
+    let font = parse_bdf(BDF, Color::WHITE).unwrap();
+    assert_eq!(font.line_height(), 4);
+    assert_eq!(font.text_width("iy"), 6);
+    let y = font.glyph('y').unwrap();
+    assert_eq!(y.offset, V2I::new(0, 1));
+    let opaque: Vec<bool> = (0..3).map(|x| opaque_at(&y.image, x, 0)).collect();
+    assert_eq!(opaque, vec![true, false, true]);
+    // no replacement glyph, missing characters fall back to the other case or nothing
+    assert_eq!(font.text_width("I?"), 2);
+  }
+
+  #[test]
+  fn reports_bdf_errors() {
+    assert_eq!(
+      parse_bdf("BBX 1 1 0 0\nBITMAP\n80\n", Color::WHITE).err(),
+      Some(BdfError::MissingBoundingBox)
+    );
+    assert_eq!(
+      parse_bdf(&BDF.replace("A0", "Z0"), Color::WHITE).err(),
+      Some(BdfError::InvalidLine(22, "Z0".to_string()))
+    );
+  }
+
+  #[test]
+  fn trims_glyph_sheet_cells() {
+    let palette = [('o', Color::WHITE)].iter().cloned().collect();
+    let sheet = Image::from_strvec(&palette, &[" o     oo ", " o      o "]);
+    let font = Font::from_glyph_sheet(&sheet, V2U::new(5, 2), "ij").unwrap();
+    assert_eq!(font.glyph('i').unwrap().advance, 2);
+    assert_eq!(font.glyph('j').unwrap().advance, 3);
+    assert_eq!(font.text_width("iji"), 7);
+    for cell_size in [
+      V2U::new(0, 2),
+      V2U::new(5, 0),
+      V2U::new(11, 2),
+      V2U::new(5, 3),
+    ] {
+      assert!(Font::from_glyph_sheet(&sheet, cell_size, "ij").is_err());
+    }
+  }
+
+  #[test]
+  fn parses_markup() {
+    assert_eq!(
+      parse_markup("a^rb^.c^^d^"),
+      vec![
+        (None, "a".to_string()),
+        (Some('r'), "b".to_string()),
+        (None, "c^d^".to_string()),
+      ]
+    );
+    let font = Font::builtin(Color::WHITE);
+    assert_eq!(
+      font.markup_width("^rscore^.: 1"),
+      font.text_width("score: 1")
+    );
+  }
+
+  #[test]
+  fn draws_colored_markup() {
+    let font = Font::builtin(Color::WHITE);
+    let red = Color::RGB(255, 0, 0);
+    let palette = [('r', red)].iter().cloned().collect();
+    let mut image = Image::filled(10, 5, Color::BLACK);
+    font.draw_markup(&mut image, 0, 0, "^r1^.1", &palette);
+    // the 1 glyph has its stem in the last column
+    assert_eq!(image.get(3, 4), Some(red));
+    assert_eq!(image.get(8, 4), Some(Color::WHITE));
+  }
+
+  #[test]
+  fn builtin_is_fixed_width() {
+    let font = Font::builtin(Color::WHITE);
+    assert_eq!(font.text_width("Score: 1"), 8 * (FONT_WIDTH + 1));
+    assert_eq!(font.text_width("\u{e9}"), FONT_WIDTH + 1);
+  }
+}
@@ -0,0 +1,145 @@
+use crate::beeper::*;
+use crate::gcontext::*;
+use crate::input_map::*;
+use crate::key_status::*;
+use crate::rng::*;
+use crate::scene::*;
+
+// A game, or one of its screens. `init` is called when the state becomes active, the other
+// methods once per event, game tick and frame. An overlay, like a pause menu, is rendered on
+// top of the scene below it.
+pub trait GameState {
+  fn init(&mut self, _gcontext: &mut GContext) {}
+  fn update(&mut self, _ctx: &mut UpdateContext) {}
+  fn render(&self, _gcontext: &mut GContext) {}
+  fn on_event(&mut self, _ctx: &mut UpdateContext, _event: &sdl2::event::Event) {}
+  fn on_quit(&mut self) {}
+  fn is_overlay(&self) -> bool {
+    false
+  }
+}
+
+pub enum Request {
+  Quit,
+  SetWindowTitle(String),
+  ChangeState(Box<dyn GameState>),
+  ChangeScene(SceneChange, Transition),
+  PlaySfx(SfxName),
+}
+
+// what game logic can see and ask for, requests are carried out after the call returns
+pub struct UpdateContext<'c> {
+  pub key_status: &'c KeyStatus,
+  pub actions: Actions<'c>,
+  pub tick: u32,
+  pub rng: &'c mut GameRng,
+  requests: Vec<Request>,
+}
+
+impl<'c> UpdateContext<'c> {
+  pub fn new(
+    key_status: &'c KeyStatus,
+    input_map: &'c InputMap,
+    tick: u32,
+    rng: &'c mut GameRng,
+  ) -> UpdateContext<'c> {
+    UpdateContext {
+      key_status,
+      actions: Actions::new(key_status, input_map),
+      tick,
+      rng,
+      requests: Vec::new(),
+    }
+  }
+
+  pub fn quit(&mut self) {
+    self.requests.push(Request::Quit);
+  }
+
+  pub fn set_window_title(&mut self, title: &str) {
+    self
+      .requests
+      .push(Request::SetWindowTitle(title.to_string()));
+  }
+
+  pub fn play_sfx(&mut self, sfx_name: SfxName) {
+    self.requests.push(Request::PlaySfx(sfx_name));
+  }
+
+  // replaces the running state and all scenes, its `init` is called before the next tick
+  pub fn change_state<G: GameState + 'static>(&mut self, state: G) {
+    self.requests.push(Request::ChangeState(Box::new(state)));
+  }
+
+  pub fn push_scene<G: GameState + 'static>(&mut self, scene: G, transition: Transition) {
+    self.change_scene(SceneChange::Push(Box::new(scene)), transition);
+  }
+
+  // the game quits when the last scene is popped
+  pub fn pop_scene(&mut self, transition: Transition) {
+    self.change_scene(SceneChange::Pop, transition);
+  }
+
+  pub fn replace_scene<G: GameState + 'static>(&mut self, scene: G, transition: Transition) {
+    self.change_scene(SceneChange::Replace(Box::new(scene)), transition);
+  }
+
+  fn change_scene(&mut self, change: SceneChange, transition: Transition) {
+    self.requests.push(Request::ChangeScene(change, transition));
+  }
+
+  pub fn into_requests(self) -> Vec<Request> {
+    self.requests
+  }
+}
+
+// adapts the closures passed to `run` to a `GameState`
+pub struct FnGame<S, I, U, R, H> {
+  state: S,
+  init: I,
+  update: U,
+  render: R,
+  handle_event: H,
+}
+
+impl<S, I, U, R, H> FnGame<S, I, U, R, H>
+where
+  I: Fn(&mut GContext),
+  U: Fn(&mut S, &KeyStatus, u32, &mut GameRng),
+  R: Fn(&mut GContext, &S),
+  H: Fn(&mut S, &sdl2::event::Event),
+{
+  pub fn new(state: S, init: I, update: U, render: R, handle_event: H) -> FnGame<S, I, U, R, H> {
+    FnGame {
+      state,
+      init,
+      update,
+      render,
+      handle_event,
+    }
+  }
+}
+
+impl<S, I, U, R, H> GameState for FnGame<S, I, U, R, H>
+where
+  I: Fn(&mut GContext),
+  U: Fn(&mut S, &KeyStatus, u32, &mut GameRng),
+  R: Fn(&mut GContext, &S),
+  H: Fn(&mut S, &sdl2::event::Event),
+{
+  fn init(&mut self, gcontext: &mut GContext) {
+    (self.init)(gcontext);
+  }
+
+  fn update(&mut self, ctx: &mut UpdateContext) {
+    (self.update)(&mut self.state, ctx.key_status, ctx.tick, ctx.rng);
+  }
+
+  fn render(&self, gcontext: &mut GContext) {
+    (self.render)(gcontext, &self.state);
+  }
+
+  fn on_event(&mut self, _ctx: &mut UpdateContext, event: &sdl2::event::Event) {
+    (self.handle_event)(&mut self.state, event);
+  }
+}
@@ -1,294 +1,157 @@
-use crate::config::*;
+use crate::animation::*;
+use crate::assets::*;
+use crate::beeper::*;
+use crate::error::*;
+use crate::font::*;
+use crate::image::*;
+use crate::input_map::*;
+use crate::keypad::*;
+use crate::pack::*;
+use crate::renderer::*;
+use crate::replay::*;
+use crate::rng::*;
+use crate::rtttl::*;
 use crate::types::*;
 use crate::Config;
 use crate::KeyStatus;
-use sdl2::rect::Rect;
+use sdl2::event::Event;
+use sdl2::mouse::MouseButton;
 use std::collections::HashMap;
 
-pub type Palette = HashMap<char, sdl2::pixels::Color>;
+// SDL_TOUCH_MOUSEID, the `which` of mouse events synthesized from touches
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
 
 pub struct GContext<'a> {
   pub ms_since_start_last_frame: u32,
   pub ms_until_game_tick: u32,
   pub game_tick_counter: u32,
 
-  pub event_pump: sdl2::EventPump,
-  pub texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext>,
-  pub screen_buffer: sdl2::render::Texture,
   pub pixel_data_surface: sdl2::surface::Surface<'a>,
-  pub timer_subsystem: sdl2::TimerSubsystem,
-  pub canvas: sdl2::render::Canvas<sdl2::video::Window>,
+  // None when running headless
+  pub display: Option<Display>,
   config: Config,
   pub window_size: V2U,
   pub camera: P2I,
-  font_sprite: sdl2::surface::Surface<'a>,
-  surface_store: HashMap<SurfaceName, sdl2::surface::Surface<'a>>,
-  sprite_sheet_store: HashMap<SpriteSheetName, SheetData>,
-  sprite_store: HashMap<SpriteName, SpriteData>,
+  pub assets: Assets,
   pub want_to_quit: bool,
   pub key_status: KeyStatus,
+  pub input_map: InputMap,
+  pub keypad: Keypad,
+  sounds: HashMap<SfxName, Sound>,
+  pub rng: GameRng,
 }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct SurfaceName(pub String);
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct SpriteSheetName(pub String);
-#[derive(Eq, PartialEq, Hash, Clone)]
-pub struct SpriteName(pub String);
-
-pub fn surface(name: &str) -> SurfaceName {
-  SurfaceName(name.to_string())
-}
-
-pub fn sprite_sheet(name: &str) -> SpriteSheetName {
-  SpriteSheetName(name.to_string())
-}
-
-pub fn sprite(name: &str) -> SpriteName {
-  SpriteName(name.to_string())
-}
-
-struct SheetData {
-  surface_name: SurfaceName,
-  size: V2U,
-}
-
-struct SpriteData {
-  sheet_name: SpriteSheetName,
-  sheet_coords: V2U,
+pub struct Display {
+  pub event_pump: sdl2::EventPump,
+  pub timer_subsystem: sdl2::TimerSubsystem,
+  pub game_controller_subsystem: sdl2::GameControllerSubsystem,
+  // by joystick instance id, controllers are closed when dropped
+  pub controllers: HashMap<u32, sdl2::controller::GameController>,
+  pub texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext>,
+  pub screen_buffer: sdl2::render::Texture,
+  pub keypad_buffer: sdl2::render::Texture,
+  pub canvas: sdl2::render::Canvas<sdl2::video::Window>,
+  pub audio_device: Option<sdl2::audio::AudioDevice<Beeper>>,
 }
 
 impl<'a> GContext<'a> {
+  // panics with the error, see `try_new`
   pub fn new(config: Config) -> GContext<'a> {
-    let sdl_context = sdl2::init().unwrap();
-    let event_pump = sdl_context.event_pump().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let timer_subsystem = sdl_context.timer().unwrap();
-    let window_size = config.screen_size * config.scale;
-
-    let window = video_subsystem
-      .window("", window_size.x, window_size.y)
-      .position_centered()
-      .resizable()
-      .build()
-      .map_err(|e| e.to_string())
-      .unwrap();
-
-    let canvas = window
-      .into_canvas()
-      .target_texture()
-      .present_vsync()
-      .build()
-      .map_err(|e| e.to_string())
-      .unwrap();
+    GContext::try_new(config).unwrap_or_else(|e| panic!("{}", e))
+  }
 
-    let texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext> =
-      canvas.texture_creator();
+  pub fn try_new(config: Config) -> Result<GContext<'a>, GameError> {
+    let mut window_size = config.screen_size * config.scale;
+    if config.on_screen_keypad {
+      window_size.y *= 2;
+    }
 
-    let screen_buffer = texture_creator
-      .create_texture_streaming(
-        sdl2::pixels::PixelFormatEnum::BGR888,
-        config.screen_size.x,
-        config.screen_size.y,
-      )
-      .unwrap();
+    let display = if config.headless {
+      None
+    } else {
+      Some(Display::new(&config, window_size)?)
+    };
 
     let pixel_data_surface = sdl2::surface::Surface::new(
       config.screen_size.x,
       config.screen_size.y,
       sdl2::pixels::PixelFormatEnum::BGR888,
     )
-    .unwrap();
+    .map_err(|e| GameError::Sdl("create the screen surface", e))?;
 
-    let font_sprite = surface_from_strvec(
-      &[('o', config.font_color)].iter().cloned().collect(),
-      FONT_DATA,
-    );
+    let assets = Assets::new(config.font_color);
 
-    GContext {
+    Ok(GContext {
       ms_since_start_last_frame: 0,
       ms_until_game_tick: 0,
       game_tick_counter: 0,
-      event_pump,
-      timer_subsystem,
-      texture_creator,
-      screen_buffer,
       pixel_data_surface,
-      canvas,
+      display,
       config,
       window_size,
       camera: P2I::new(0, 0),
-      font_sprite,
-      sprite_store: HashMap::new(),
-      sprite_sheet_store: HashMap::new(),
-      surface_store: HashMap::new(),
+      assets,
       want_to_quit: false,
       key_status: KeyStatus::new(),
-    }
+      input_map: InputMap::new(),
+      keypad: Keypad::new(),
+      sounds: HashMap::new(),
+      rng: GameRng::new(session_seed()),
+    })
   }
 
-  pub fn reset_screen(&mut self) {
-    self
-      .pixel_data_surface
-      .fill_rect(None, self.config.background_color)
-      .unwrap();
+  pub fn is_headless(&self) -> bool {
+    self.display.is_none()
   }
 
-  pub fn take_screenshot(canvas: &sdl2::render::Canvas<sdl2::video::Window>) {
-    let pic_data = canvas
-      .read_pixels(None, sdl2::pixels::PixelFormatEnum::ABGR8888)
-      .unwrap();
-    let path = std::path::Path::new(r"screenshot.png");
-    let file = std::fs::File::create(path).unwrap();
-    let ref mut w = std::io::BufWriter::new(file);
-
-    let (width, height) = canvas.window().size();
-
-    let mut encoder = png::Encoder::new(w, width, height);
-    encoder.set_color(png::ColorType::RGBA);
-    encoder.set_depth(png::BitDepth::Eight);
-    let mut writer = encoder.write_header().unwrap();
-
-    writer.write_image_data(&pic_data).unwrap();
-  }
-
-  fn set_pixel(&mut self, x: i32, y: i32, color: sdl2::pixels::Color) {
-    if x < 0
-      || self.config.screen_size.x as i32 <= x
-      || y < 0
-      || self.config.screen_size.y as i32 <= y
-    {
-      return;
+  // headless contexts have no timer, time advances by `frame_ms` every frame instead
+  pub fn ms_since_start(&mut self, frame_ms: u32) -> u32 {
+    match &mut self.display {
+      Some(display) => display.timer_subsystem.ticks(),
+      None => self.ms_since_start_last_frame + frame_ms,
     }
-    let screen_width = self.config.screen_size.x as i32;
-    self.pixel_data_surface.with_lock_mut(|surf: &mut [u8]| {
-      let off = (x + y * screen_width) as usize * 4;
-      surf[off + 0] = color.b;
-      surf[off + 1] = color.g;
-      surf[off + 2] = color.r;
-    });
   }
 
-  // midpoint circle algorithm
-  pub fn draw_circle(&mut self, cx: i32, cy: i32, r: i32, m: i32, color: sdl2::pixels::Color) {
-    let mut f = 1 - r;
-    let mut dx = 0;
-    let mut dy = -2 * r;
-    let mut x = 0;
-    let mut y = r;
-
-    self.set_pixel(cx, cy + r, color);
-    self.set_pixel(cx, cy - r, color);
-    self.set_pixel(cx + r, cy, color);
-    self.set_pixel(cx - r, cy, color);
-
-    while x < y {
-      if f >= 0 {
-        y -= 1;
-        dy += 2;
-        f += dy;
-      }
-      x += 1;
-      dx += 2;
-      f += dx + 1;
-      if x % m == 0 {
-        self.set_pixel(cx + x, cy + y, color);
-        self.set_pixel(cx - x, cy + y, color);
-        self.set_pixel(cx + x, cy - y, color);
-        self.set_pixel(cx - x, cy - y, color);
-        self.set_pixel(cx + y, cy + x, color);
-        self.set_pixel(cx - y, cy + x, color);
-        self.set_pixel(cx + y, cy - x, color);
-        self.set_pixel(cx - y, cy - x, color);
-      }
-    }
+  pub fn reset_screen(&mut self) {
+    let screen_size = self.config.screen_size;
+    let background_color = self.config.background_color;
+    self.draw_rect(0, 0, screen_size.x, screen_size.y, background_color);
   }
 
-  pub fn draw_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: sdl2::pixels::Color) {
-    self
-      .pixel_data_surface
-      .fill_rect(sdl2::rect::Rect::new(x, y, w, h), color)
-      .unwrap();
+  pub fn screen_image(&self) -> Image {
+    Image::from_surface(&self.pixel_data_surface)
   }
 
-  pub fn draw_text(&mut self, x: i32, y: i32, text: &str) {
-    let mut i = 0;
-    for ch in text.chars() {
-      if ch != ' ' {
-        let ix = if '0' <= ch && ch <= '9' {
-          ch as u8 - '0' as u8
-        } else {
-          10 + ch as u8 - 'a' as u8
-        };
-        let ix_x = ix % 10;
-        let ix_y = ix / 10;
-        self
-          .font_sprite
-          .blit(
-            Rect::new(
-              ix_x as i32 * FONT_WIDTH as i32,
-              ix_y as i32 * FONT_HEIGHT as i32,
-              FONT_WIDTH,
-              FONT_HEIGHT,
-            ),
-            &mut self.pixel_data_surface,
-            sdl2::rect::Rect::new(x + i * (1 + FONT_WIDTH as i32), y, FONT_WIDTH, 5),
-          )
-          .unwrap();
-      }
-      i += 1;
-    }
+  pub fn take_screenshot(
+    canvas: &sdl2::render::Canvas<sdl2::video::Window>,
+  ) -> Result<(), GameError> {
+    let pic_data = canvas
+      .read_pixels(None, sdl2::pixels::PixelFormatEnum::ABGR8888)
+      .map_err(|e| GameError::Sdl("read the window pixels", e))?;
+    let (width, height) = canvas.window().size();
+    write_png(
+      std::path::Path::new(r"screenshot.png"),
+      width,
+      height,
+      &pic_data,
+    )
   }
 
-  pub fn draw_text_box<T: AsRef<str>>(
+  pub fn add_surface(
     &mut self,
-    hor_pos: HorPos,
-    vert_pos: VertPos,
-    lines: &[T],
-    background_color: sdl2::pixels::Color,
-  ) {
-    let text_box_w: u32 = lines
-      .iter()
-      .max_by(|x, y| x.as_ref().len().partial_cmp(&y.as_ref().len()).unwrap())
-      .map(|x| x.as_ref().len())
-      .unwrap_or(0) as u32
-      * (FONT_WIDTH + 1)
-      + 1;
-    let text_box_h: u32 = lines.len() as u32 * (FONT_HEIGHT + 1) + 1;
-    let dialogue_x = match hor_pos {
-      HorPos::Left => 0,
-      HorPos::Center => (self.get_config().screen_size.x as i32 - text_box_w as i32) / 2,
-      HorPos::Right => self.get_config().screen_size.x as i32 - text_box_w as i32,
-      HorPos::Abs { x } => x,
-    };
-    let dialogue_y = match vert_pos {
-      VertPos::Top => 0,
-      VertPos::Center => (self.get_config().screen_size.y as i32 - text_box_h as i32) / 2,
-      VertPos::Bottom => self.get_config().screen_size.y as i32 - text_box_h as i32,
-      VertPos::Abs { y } => y,
-    };
-    self.draw_rect(
-      dialogue_x,
-      dialogue_y,
-      text_box_w,
-      text_box_h,
-      background_color,
-    );
-    let mut ix = 0;
-    for line in lines {
-      self.draw_text(
-        dialogue_x + 1,
-        dialogue_y + ix * (FONT_HEIGHT as i32 + 1) + 1,
-        line.as_ref(),
-      );
-      ix += 1;
-    }
+    surface_name: SurfaceName,
+    palette: &Palette,
+    data: Vec<&str>,
+  ) -> SurfaceId {
+    self.assets.add_surface(surface_name, palette, data)
   }
 
-  pub fn add_surface(&mut self, surface_name: SurfaceName, palette: &Palette, data: Vec<&str>) {
-    self
-      .surface_store
-      .insert(surface_name, surface_from_strvec(palette, &data));
+  pub fn add_surface_file<P: AsRef<std::path::Path>>(
+    &mut self,
+    surface_name: SurfaceName,
+    path: P,
+  ) -> Result<SurfaceId, GameError> {
+    self.assets.add_surface_file(surface_name, path)
   }
 
   pub fn add_sprite_sheet(
@@ -296,85 +159,265 @@ impl<'a> GContext<'a> {
     sprite_sheet_name: SpriteSheetName,
     sprite_sheet_path: &str,
     size: V2U,
-  ) {
-    let surface_name = SurfaceName("#".to_owned() + &sprite_sheet_name.0);
-
-    self
-      .surface_store
-      .insert(surface_name.clone(), load_surface(sprite_sheet_path));
-
+  ) -> Result<SpriteSheetId, GameError> {
     self
-      .sprite_sheet_store
-      .insert(sprite_sheet_name, SheetData { size, surface_name });
+      .assets
+      .add_sprite_sheet(sprite_sheet_name, sprite_sheet_path, size)
   }
 
   pub fn add_sprite(
     &mut self,
-    sprite_sheet_name: SpriteSheetName,
+    sprite_sheet_id: SpriteSheetId,
     sprite_name: SpriteName,
     sheet_x: u32,
     sheet_y: u32,
-  ) {
-    self.sprite_store.insert(
-      sprite_name,
-      SpriteData {
-        sheet_coords: V2U::new(sheet_x, sheet_y),
-        sheet_name: sprite_sheet_name,
-      },
-    );
+  ) -> SpriteId {
+    self
+      .assets
+      .add_sprite(sprite_sheet_id, sprite_name, sheet_x, sheet_y)
   }
 
-  pub fn draw_surface(&mut self, x: i32, y: i32, surface_name: SurfaceName) {
-    let surface = self.surface_store.get(&surface_name).unwrap();
-    surface
-      .blit(
-        None,
-        &mut self.pixel_data_surface,
-        sdl2::rect::Rect::new(x, y, surface.width(), surface.height()),
-      )
-      .unwrap();
+  pub fn add_sprite_sheet_json(
+    &mut self,
+    sprite_sheet_name: SpriteSheetName,
+    json_path: &str,
+  ) -> Result<SpriteSheetId, GameError> {
+    self
+      .assets
+      .add_sprite_sheet_json(sprite_sheet_name, json_path)
   }
 
-  pub fn draw_sprite(&mut self, x: i32, y: i32, sprite_name: SpriteName) {
-    let sprite = self.sprite_store.get(&sprite_name).unwrap();
-    let sprite_sheet = self.sprite_sheet_store.get(&sprite.sheet_name).unwrap();
-    let sprite_sheet_surface = self.surface_store.get(&sprite_sheet.surface_name).unwrap();
-    let sprite_w = sprite_sheet_surface.width() / sprite_sheet.size.x;
-    let sprite_h = sprite_sheet_surface.height() / sprite_sheet.size.y;
-    let sprite_x = sprite_w * sprite.sheet_coords.x;
-    let sprite_y = sprite_h * sprite.sheet_coords.y;
-    sprite_sheet_surface
-      .blit(
-        sdl2::rect::Rect::new(sprite_x as i32, sprite_y as i32, sprite_w, sprite_h),
-        &mut self.pixel_data_surface,
-        sdl2::rect::Rect::new(x, y, sprite_w, sprite_h),
-      )
-      .unwrap();
+  pub fn add_animation(
+    &mut self,
+    animation_name: AnimationName,
+    animation: Animation,
+  ) -> AnimationId {
+    self.assets.add_animation(animation_name, animation)
+  }
+
+  pub fn add_font(&mut self, font_name: FontName, font: Font) -> FontId {
+    self.assets.add_font(font_name, font)
+  }
+
+  // Adds everything listed in the pack's manifest, fonts in the configured font color. The
+  // handles can be looked up by name afterwards, e.g. with `Assets::surface_id`.
+  pub fn add_asset_pack(&mut self, pack: &AssetPack) -> Result<(), PackError> {
+    let invalid_file = |file: &str, e: &dyn std::fmt::Display| {
+      PackError::InvalidFile(file.to_string(), e.to_string())
+    };
+    for entry in pack.manifest()? {
+      match entry {
+        ManifestEntry::Surface { name, file } => {
+          self
+            .assets
+            .add_surface_from_pack(pack, surface(&name), &file)?;
+        }
+        ManifestEntry::SpriteSheet { name, file, cells } => match cells {
+          Some(cells) => {
+            self
+              .assets
+              .add_sprite_sheet_from_pack(pack, sprite_sheet(&name), &file, cells)?;
+          }
+          None => {
+            self
+              .assets
+              .add_sprite_sheet_json_from_pack(pack, sprite_sheet(&name), &file)?;
+          }
+        },
+        ManifestEntry::Font { name, file, glyphs } => {
+          let loaded = match glyphs {
+            Some((cell_size, chars)) => {
+              Font::glyph_sheet_from_pack(pack, &file, cell_size, &chars)?
+            }
+            None => Font::bdf_from_pack(pack, &file, self.config.font_color)?,
+          };
+          self.assets.add_font(font(&name), loaded);
+        }
+        ManifestEntry::Sfx { name, file } => {
+          let ringtone = parse_rtttl(pack.text(&file)?).map_err(|e| invalid_file(&file, &e))?;
+          self.add_sfx(sfx(&name), ringtone.to_sound());
+        }
+      }
+    }
+    Ok(())
+  }
+
+  pub fn add_sfx(&mut self, sfx_name: SfxName, sound: Sound) {
+    self.sounds.insert(sfx_name, sound);
+  }
+
+  // unknown sounds are logged, the game keeps running without them
+  pub fn play_sfx(&mut self, sfx_name: &SfxName) {
+    match self.sounds.get(sfx_name) {
+      Some(sound) => self.play_sound(sound.clone()),
+      None => log_error(format!("unknown sfx '{}'", sfx_name.0)),
+    }
   }
 
+  // cuts off the sound playing, does nothing without an audio device
+  pub fn play_sound(&mut self, sound: Sound) {
+    if let Some(Display {
+      audio_device: Some(audio_device),
+      ..
+    }) = &mut self.display
+    {
+      audio_device.lock().play(sound);
+    }
+  }
+
+  // a frame that fails to draw is logged and skipped
   pub fn present(&mut self) {
-    let screen_buffer = &mut self.screen_buffer;
+    if let Err(e) = self.try_present() {
+      log_error(e);
+    }
+  }
+
+  fn try_present(&mut self) -> Result<(), GameError> {
+    let display = match &mut self.display {
+      Some(display) => display,
+      None => return Ok(()),
+    };
+    let screen_buffer = &mut display.screen_buffer;
     let screen_width = self.config.screen_size.x;
-    self.pixel_data_surface.with_lock(|surf: &[u8]| {
-      screen_buffer
-        .update(None, surf, (screen_width * 4) as usize)
-        .unwrap();
-    });
-    let scaled_screen_size = self.config.screen_size * self.config.scale;
     self
+      .pixel_data_surface
+      .with_lock(|surf: &[u8]| screen_buffer.update(None, surf, (screen_width * 4) as usize))
+      .map_err(|e| GameError::Sdl("update the screen texture", e.to_string()))?;
+    let (lcd_area, keypad_area) = layout(&self.config, self.window_size);
+    display
       .canvas
-      .copy(
-        &self.screen_buffer,
-        None,
-        sdl2::rect::Rect::new(
-          (self.window_size.x as i32 - scaled_screen_size.x as i32) / 2,
-          (self.window_size.y as i32 - scaled_screen_size.y as i32) / 2,
-          scaled_screen_size.x,
-          scaled_screen_size.y,
-        ),
-      )
-      .unwrap();
-    self.canvas.present();
+      .copy(&display.screen_buffer, None, lcd_area)
+      .map_err(|e| GameError::Sdl("draw the screen", e))?;
+    if let Some(keypad_area) = keypad_area {
+      let keypad_image = self.keypad.render(
+        &self.assets,
+        &self.key_status,
+        self.config.screen_size,
+        self.config.font_color,
+        self.config.background_color,
+      );
+      display
+        .keypad_buffer
+        .update(
+          None,
+          &keypad_image.to_rgba_bytes(),
+          (keypad_image.width() * 4) as usize,
+        )
+        .map_err(|e| GameError::Sdl("update the keypad texture", e.to_string()))?;
+      display
+        .canvas
+        .copy(&display.keypad_buffer, None, keypad_area)
+        .map_err(|e| GameError::Sdl("draw the keypad", e))?;
+    }
+    display.canvas.present();
+    Ok(())
+  }
+
+  pub fn open_controller(&mut self, joystick_index: u32) {
+    if let Some(display) = &mut self.display {
+      let subsystem = &display.game_controller_subsystem;
+      if subsystem.is_game_controller(joystick_index) {
+        if let Ok(controller) = subsystem.open(joystick_index) {
+          display
+            .controllers
+            .insert(controller.instance_id(), controller);
+        }
+      }
+    }
+  }
+
+  pub fn close_controller(&mut self, instance_id: u32) {
+    if let Some(display) = &mut self.display {
+      display.controllers.remove(&instance_id);
+    }
+  }
+
+  // The input events for an sdl event, none if it is not about input. Clicks on the
+  // on-screen keypad only press phone keys. Touches act as the left mouse button, and all
+  // positions are converted to screen pixels.
+  pub fn input_events(&mut self, event: &Event) -> Option<Vec<InputEvent>> {
+    let (_, keypad_area) = layout(&self.config, self.window_size);
+    if let Some(keypad_area) = keypad_area {
+      if let Some(input_event) = self.keypad.on_screen_event(event, keypad_area) {
+        return Some(vec![input_event]);
+      }
+    }
+    let pointer_motion = |x: f32, y: f32| {
+      let pos = self.window_to_screen(x, y);
+      InputEvent::PointerMotion { x: pos.x, y: pos.y }
+    };
+    let finger_position =
+      |x: f32, y: f32| (x * self.window_size.x as f32, y * self.window_size.y as f32);
+    let input_events = match *event {
+      // touches also come as mouse events, those are skipped for the finger events
+      Event::MouseMotion { which, .. }
+      | Event::MouseButtonDown { which, .. }
+      | Event::MouseButtonUp { which, .. }
+        if which == TOUCH_MOUSE_ID =>
+      {
+        vec![]
+      }
+      Event::MouseMotion { x, y, .. } => vec![pointer_motion(x as f32, y as f32)],
+      Event::MouseButtonDown {
+        mouse_btn, x, y, ..
+      } => vec![
+        pointer_motion(x as f32, y as f32),
+        InputEvent::MouseButtonDown { button: mouse_btn },
+      ],
+      Event::MouseButtonUp {
+        mouse_btn, x, y, ..
+      } => vec![
+        pointer_motion(x as f32, y as f32),
+        InputEvent::MouseButtonUp { button: mouse_btn },
+      ],
+      Event::FingerDown { x, y, .. } => {
+        let (x, y) = finger_position(x, y);
+        if keypad_area.is_some_and(|area| area.contains_point((x as i32, y as i32))) {
+          vec![]
+        } else {
+          vec![
+            pointer_motion(x, y),
+            InputEvent::MouseButtonDown {
+              button: MouseButton::Left,
+            },
+          ]
+        }
+      }
+      Event::FingerMotion { x, y, .. } => {
+        let (x, y) = finger_position(x, y);
+        vec![pointer_motion(x, y)]
+      }
+      Event::FingerUp { x, y, .. } => {
+        let (x, y) = finger_position(x, y);
+        vec![
+          pointer_motion(x, y),
+          InputEvent::MouseButtonUp {
+            button: MouseButton::Left,
+          },
+        ]
+      }
+      _ => vec![InputEvent::from_event(event)?],
+    };
+    Some(input_events)
+  }
+
+  // window coordinates to screen pixels, the result is outside of the screen when the
+  // position is in the letterbox
+  pub fn window_to_screen(&self, x: f32, y: f32) -> P2I {
+    let (lcd_area, _) = layout(&self.config, self.window_size);
+    let scale = self.config.scale as f32;
+    P2I::new(
+      ((x - lcd_area.x() as f32) / scale).floor() as i32,
+      ((y - lcd_area.y() as f32) / scale).floor() as i32,
+    )
+  }
+
+  pub fn set_window_title(&mut self, title: &str) {
+    if let Some(display) = &mut self.display {
+      if let Err(e) = display.canvas.window_mut().set_title(title) {
+        log_error(GameError::Sdl("set the window title", e.to_string()));
+      }
+    }
   }
 
   pub fn get_config(&self) -> &Config {
@@ -382,76 +425,173 @@ impl<'a> GContext<'a> {
   }
 }
 
-pub enum HorPos {
-  Left,
-  Right,
-  Center,
-  Abs { x: i32 },
-}
+impl Display {
+  fn new(config: &Config, window_size: V2U) -> Result<Display, GameError> {
+    let sdl_context = sdl2::init().map_err(|e| GameError::Sdl("initialize SDL", e))?;
+    let event_pump = sdl_context
+      .event_pump()
+      .map_err(|e| GameError::Sdl("get the event pump", e))?;
+    let video_subsystem = sdl_context
+      .video()
+      .map_err(|e| GameError::Sdl("initialize video", e))?;
+    let timer_subsystem = sdl_context
+      .timer()
+      .map_err(|e| GameError::Sdl("initialize the timer", e))?;
+    // already connected controllers are reported as added by the first events
+    let game_controller_subsystem = sdl_context
+      .game_controller()
+      .map_err(|e| GameError::Sdl("initialize game controllers", e))?;
+
+    let window = video_subsystem
+      .window("", window_size.x, window_size.y)
+      .position_centered()
+      .resizable()
+      .build()
+      .map_err(|e| GameError::Sdl("create the window", e.to_string()))?;
 
-pub enum VertPos {
-  Top,
-  Bottom,
-  Center,
-  Abs { y: i32 },
+    let canvas = window
+      .into_canvas()
+      .target_texture()
+      .present_vsync()
+      .build()
+      .map_err(|e| GameError::Sdl("create the renderer", e.to_string()))?;
+
+    let texture_creator: sdl2::render::TextureCreator<sdl2::video::WindowContext> =
+      canvas.texture_creator();
+
+    let screen_buffer = texture_creator
+      .create_texture_streaming(
+        sdl2::pixels::PixelFormatEnum::BGR888,
+        config.screen_size.x,
+        config.screen_size.y,
+      )
+      .map_err(|e| GameError::Sdl("create the screen texture", e.to_string()))?;
+
+    let keypad_buffer = texture_creator
+      .create_texture_streaming(
+        sdl2::pixels::PixelFormatEnum::ABGR8888,
+        config.screen_size.x,
+        config.screen_size.y,
+      )
+      .map_err(|e| GameError::Sdl("create the keypad texture", e.to_string()))?;
+
+    Ok(Display {
+      event_pump,
+      timer_subsystem,
+      game_controller_subsystem,
+      controllers: HashMap::new(),
+      texture_creator,
+      screen_buffer,
+      keypad_buffer,
+      canvas,
+      audio_device: open_audio_device(&sdl_context),
+    })
+  }
 }
 
-fn surface_from_strvec<'a>(palette: &Palette, data: &[&str]) -> sdl2::surface::Surface<'a> {
-  let width = data[0].len() as u32;
-  let height = data.len() as u32;
-  let mut surface =
-    sdl2::surface::Surface::new(width, height, sdl2::pixels::PixelFormatEnum::ABGR8888).unwrap();
-  surface.with_lock_mut(|surf: &mut [u8]| {
-    for x in 0..width {
-      for y in 0..height {
-        let off = (x + y * width) as usize * 4;
-        let ch = data[y as usize].as_bytes()[x as usize] as char;
-        let color = palette.get(&ch).unwrap_or(&TRANSPARENT);
-        surf[off + 0] = color.r;
-        surf[off + 1] = color.g;
-        surf[off + 2] = color.b;
-        surf[off + 3] = color.a;
-      }
-    }
-  });
-  surface
+// Where the lcd and the on-screen keypad go in the window. Both are drawn at the configured
+// scale, the keypad right below the lcd and both together centered in the window.
+fn layout(config: &Config, window_size: V2U) -> (sdl2::rect::Rect, Option<sdl2::rect::Rect>) {
+  let scaled_screen_size = config.screen_size * config.scale;
+  let total_height = if config.on_screen_keypad {
+    scaled_screen_size.y * 2
+  } else {
+    scaled_screen_size.y
+  };
+  let x = (window_size.x as i32 - scaled_screen_size.x as i32) / 2;
+  let y = (window_size.y as i32 - total_height as i32) / 2;
+  let lcd_area = sdl2::rect::Rect::new(x, y, scaled_screen_size.x, scaled_screen_size.y);
+  let keypad_area = if config.on_screen_keypad {
+    Some(sdl2::rect::Rect::new(
+      x,
+      y + scaled_screen_size.y as i32,
+      scaled_screen_size.x,
+      scaled_screen_size.y,
+    ))
+  } else {
+    None
+  };
+  (lcd_area, keypad_area)
 }
 
-fn load_surface<'a>(file_path: &str) -> sdl2::surface::Surface<'a> {
-  use sdl2::image::ImageRWops;
-  sdl2::rwops::RWops::from_file(file_path, "r")
-    .unwrap()
-    .load()
-    .unwrap()
+impl<'a> Renderer for GContext<'a> {
+  fn target_and_assets(&mut self) -> (&mut dyn DrawTarget, &Assets) {
+    (&mut self.pixel_data_surface, &self.assets)
+  }
 }
 
-pub const FONT_WIDTH: u32 = 4;
-pub const FONT_HEIGHT: u32 = 5;
-
-#[rustfmt::skip]
-const FONT_DATA: &[&str] = &[
-// L   L   L   L   L   L   L   L   L   L   L
-  " oo    o oo  oo o   oooo ooooooo oo  oo ",
-  "o  o   oo  oo  oo   o   o      oo  oo  o",
-  "o  o   o  o   o o o  oo ooo   o  oo  ooo",
-  "o  o   o o  o  ooooo   oo  o o  o  o   o",
-  " oo    ooooo oo   o ooo  oo o    oo  oo ",
-
-  " oo ooo  oo ooo oooooooo oo o  o  o    o",
-  "o  oo  oo  oo  oo   o   o  oo  o  o    o",
-  "ooooooo o   o  oooo ooo o   oooo  o    o",
-  "o  oo  oo  oo  oo   o   o ooo  o  o o  o",
-  "o  oooo  oo ooo ooooo    oo o  o  o  oo ",
-
-  "o  oo   o  oo  o oo ooo  oo ooo  ooooooo",
-  "o o o   oooooo oo  oo  oo  oo  oo     o ",
-  "oo  o   oo oo ooo  oooo o  oooo  oo   o ",
-  "o o o   o  oo  oo  oo   o ooo  o   o  o ",
-  "o  oooooo  oo  o oo o    oooo  oooo   o ",
-
-  "o  oo  oo  oo  oo  ooooo                ",
-  "o  oo  oo  oo  oo  o   o                ",
-  "o  oo  oo oo oo  oo  oo                 ",
-  "o  o o o ooo oo   o o                   ",
-  " oo   o  oo o  o  o oooo                ",
-];
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::*;
+  use crate::key_status::*;
+  use sdl2::controller::{Axis, Button};
+
+  #[test]
+  fn maps_a_resized_window_to_the_screen() {
+    // 84x48 at scale 3 is 252x144, centered at (74, 78)
+    let mut gcontext = GContext::new(cell_phone_config(3).headless());
+    gcontext.window_size = V2U::new(400, 300);
+    assert_eq!(gcontext.window_to_screen(200.0, 150.0), P2I::new(42, 24));
+    assert_eq!(gcontext.window_to_screen(74.0, 78.0), P2I::new(0, 0));
+    assert_eq!(gcontext.window_to_screen(325.0, 221.0), P2I::new(83, 47));
+
+    let is_on_screen = |pos: P2I| (0..84).contains(&pos.x) && (0..48).contains(&pos.y);
+    for (x, y) in [(73.0, 150.0), (326.0, 150.0), (200.0, 77.0), (200.0, 222.0)] {
+      assert!(!is_on_screen(gcontext.window_to_screen(x, y)));
+    }
+  }
+
+  #[test]
+  fn controller_events_press_bound_actions() {
+    let mut gcontext = GContext::new(cell_phone_config(1).headless());
+    gcontext.input_map.set_bindings(
+      action("paddle_up"),
+      vec![
+        Button::DPadUp.into(),
+        Input::GamepadAxis(Axis::LeftY, AxisDirection::Negative),
+      ],
+    );
+    let mut feed = |gcontext: &mut GContext, event: Event| {
+      for input_event in gcontext.input_events(&event).unwrap() {
+        gcontext.key_status.apply(&input_event);
+      }
+    };
+    let is_pressed = |gcontext: &GContext| {
+      Actions::new(&gcontext.key_status, &gcontext.input_map).is_pressed(&action("paddle_up"))
+    };
+
+    feed(
+      &mut gcontext,
+      InputEvent::GamepadButtonDown {
+        button: Button::DPadUp,
+      }
+      .to_event()
+      .unwrap(),
+    );
+    assert!(is_pressed(&gcontext));
+    feed(
+      &mut gcontext,
+      InputEvent::GamepadButtonUp {
+        button: Button::DPadUp,
+      }
+      .to_event()
+      .unwrap(),
+    );
+    assert!(!is_pressed(&gcontext));
+
+    // inside the deadzone, then pushed up
+    let axis = |value| {
+      InputEvent::GamepadAxis {
+        axis: Axis::LeftY,
+        value,
+      }
+      .to_event()
+      .unwrap()
+    };
+    feed(&mut gcontext, axis(-2000));
+    assert!(!is_pressed(&gcontext));
+    feed(&mut gcontext, axis(-30000));
+    assert!(is_pressed(&gcontext));
+  }
+}
@@ -0,0 +1,114 @@
+// golden image snapshot testing
+//
+// Frames are rendered headless and compared to checked in pngs. Run the tests with
+// `BLESS_GOLDEN=1` to (re)write the goldens instead of comparing against them.
+use crate::color::*;
+use crate::config::*;
+use crate::error::*;
+use crate::game_state::*;
+use crate::gcontext::*;
+use crate::image::*;
+use std::path::{Path, PathBuf};
+
+pub const BLESS_GOLDEN_VAR: &str = "BLESS_GOLDEN";
+
+pub fn render_frame<G: GameState>(config: Config, state: &mut G) -> Image {
+  render_state(GContext::new(config.headless()), state)
+}
+
+// like `run_game_with`, the state is made once assets can be added, errors panic
+pub fn render_frame_with<G, F>(config: Config, make_state: F) -> Image
+where
+  G: GameState,
+  F: FnOnce(&mut GContext) -> Result<G, GameError>,
+{
+  let mut gcontext = GContext::new(config.headless());
+  let mut state = make_state(&mut gcontext).unwrap_or_else(|e| panic!("{}", e));
+  render_state(gcontext, &mut state)
+}
+
+fn render_state<G: GameState>(mut gcontext: GContext, state: &mut G) -> Image {
+  state.init(&mut gcontext);
+  gcontext.reset_screen();
+  state.render(&mut gcontext);
+  gcontext.screen_image()
+}
+
+pub fn assert_golden<P: AsRef<Path>>(image: &Image, golden_path: P) {
+  let golden_path = golden_path.as_ref();
+  if let Some(dir) = golden_path.parent() {
+    std::fs::create_dir_all(dir).unwrap();
+  }
+  let save = |image: &Image, path: &Path| image.save_png(path).unwrap_or_else(|e| panic!("{}", e));
+  if std::env::var_os(BLESS_GOLDEN_VAR).is_some() {
+    save(image, golden_path);
+    return;
+  }
+
+  if !golden_path.exists() {
+    let actual_path = sibling_path(golden_path, "actual");
+    save(image, &actual_path);
+    panic!(
+      "golden {} does not exist, rendered frame written to {}, rerun with {}=1 to create it",
+      golden_path.display(),
+      actual_path.display(),
+      BLESS_GOLDEN_VAR,
+    );
+  }
+
+  let golden = Image::load_png(golden_path).unwrap_or_else(|e| panic!("{}", e));
+  let mismatches = count_mismatches(image, &golden);
+  if mismatches > 0 {
+    let actual_path = sibling_path(golden_path, "actual");
+    let diff_path = sibling_path(golden_path, "diff");
+    save(image, &actual_path);
+    save(&diff_image(image, &golden), &diff_path);
+    panic!(
+      "frame differs from golden {} in {} pixels, see {} and {}, rerun with {}=1 to accept it",
+      golden_path.display(),
+      mismatches,
+      actual_path.display(),
+      diff_path.display(),
+      BLESS_GOLDEN_VAR,
+    );
+  }
+}
+
+fn count_mismatches(actual: &Image, golden: &Image) -> usize {
+  if actual.size() != golden.size() {
+    return actual.pixels().len().max(golden.pixels().len());
+  }
+  actual
+    .pixels()
+    .iter()
+    .zip(golden.pixels())
+    .filter(|(a, g)| a != g)
+    .count()
+}
+
+// matching pixels are faded out, differing pixels are red
+fn diff_image(actual: &Image, golden: &Image) -> Image {
+  let width = actual.width().max(golden.width());
+  let height = actual.height().max(golden.height());
+  let mut diff = Image::filled(width, height, Color::RGB(255, 0, 0));
+  for y in 0..height as i32 {
+    for x in 0..width as i32 {
+      if let (Some(a), Some(g)) = (actual.get(x, y), golden.get(x, y)) {
+        if a == g {
+          let fade = |c: u8| 192 + c / 4;
+          *diff.get_mut(x, y).unwrap() = Color::RGB(fade(a.r), fade(a.g), fade(a.b));
+        }
+      }
+    }
+  }
+  diff
+}
+
+// golden/title.png -> golden/title.actual.png
+fn sibling_path(golden_path: &Path, suffix: &str) -> PathBuf {
+  let stem = golden_path
+    .file_stem()
+    .map(|s| s.to_string_lossy().into_owned())
+    .unwrap_or_default();
+  golden_path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
@@ -0,0 +1,224 @@
+use crate::arr2d::*;
+use crate::ascii_surface::*;
+use crate::color::*;
+use crate::config::*;
+use crate::error::*;
+use crate::types::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub type Palette = HashMap<char, Color>;
+
+// plain RGBA buffer, used for assets and as an offscreen draw target
+#[derive(Clone)]
+pub struct Image {
+  pixels: Arr2d<Color>,
+}
+
+// a rectangular region of an image
+pub struct ImageRegion<'a> {
+  pub image: &'a Image,
+  pub pos: P2U,
+  pub size: V2U,
+}
+
+impl Image {
+  pub fn new(width: u32, height: u32) -> Image {
+    Image::filled(width, height, TRANSPARENT)
+  }
+
+  pub fn filled(width: u32, height: u32, color: Color) -> Image {
+    Image {
+      pixels: Arr2d::new(width, height, color),
+    }
+  }
+
+  // panics unless all rows are equally wide
+  pub fn from_strvec(palette: &Palette, data: &[&str]) -> Image {
+    Image::try_from_strvec(palette, data).unwrap_or_else(|e| panic!("invalid surface: {}", e))
+  }
+
+  // characters missing from the palette are transparent, rows are numbered as lines from 1
+  pub fn try_from_strvec(palette: &Palette, data: &[&str]) -> Result<Image, AsciiSurfaceError> {
+    let expected = data
+      .first()
+      .ok_or(AsciiSurfaceError::NoRows)?
+      .chars()
+      .count();
+    if let Some((ix, row)) = data
+      .iter()
+      .enumerate()
+      .find(|(_, row)| row.chars().count() != expected)
+    {
+      return Err(AsciiSurfaceError::UnevenRow {
+        line: ix + 1,
+        width: row.chars().count(),
+        expected,
+      });
+    }
+    let mut image = Image::new(expected as u32, data.len() as u32);
+    for (y, row) in data.iter().enumerate() {
+      for (x, ch) in row.chars().enumerate() {
+        let color = palette.get(&ch).unwrap_or(&TRANSPARENT);
+        *image.pixels.get_mut_unsafe(x as u32, y as u32) = *color;
+      }
+    }
+    Ok(image)
+  }
+
+  // panics if SDL can't convert the surface, see `try_from_surface`
+  pub fn from_surface(surface: &sdl2::surface::Surface) -> Image {
+    Image::try_from_surface(surface).unwrap_or_else(|e| panic!("can't convert surface: {}", e))
+  }
+
+  pub fn try_from_surface(surface: &sdl2::surface::Surface) -> Result<Image, String> {
+    let surface = surface.convert_format(sdl2::pixels::PixelFormatEnum::ABGR8888)?;
+    let mut image = Image::new(surface.width(), surface.height());
+    let pitch = surface.pitch() as usize;
+    surface.with_lock(|surf: &[u8]| {
+      for y in 0..image.height() {
+        for x in 0..image.width() {
+          let off = y as usize * pitch + x as usize * 4;
+          *image.pixels.get_mut_unsafe(x, y) =
+            Color::RGBA(surf[off], surf[off + 1], surf[off + 2], surf[off + 3]);
+        }
+      }
+    });
+    Ok(image)
+  }
+
+  pub fn load_png<P: AsRef<Path>>(path: P) -> Result<Image, GameError> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(|e| GameError::io(path, e))?;
+    Image::decode_png(file).map_err(|e| GameError::invalid_file(path, e))
+  }
+
+  // only RGB and RGBA pngs are supported, 16 bit ones are read as 8 bit
+  pub fn decode_png<R: std::io::Read>(reader: R) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(reader);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(|e| e.to_string())?;
+    if info.bit_depth != png::BitDepth::Eight {
+      return Err(format!("unsupported png bit depth {:?}", info.bit_depth));
+    }
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data).map_err(|e| e.to_string())?;
+    let channels = match info.color_type {
+      png::ColorType::RGB => 3,
+      png::ColorType::RGBA => 4,
+      color_type => return Err(format!("unsupported png color type {:?}", color_type)),
+    };
+    let mut image = Image::new(info.width, info.height);
+    for (ix, px) in data.chunks(channels).enumerate() {
+      let x = ix as u32 % info.width;
+      let y = ix as u32 / info.width;
+      let a = if channels == 4 { px[3] } else { 255 };
+      *image.pixels.get_mut_unsafe(x, y) = Color::RGBA(px[0], px[1], px[2], a);
+    }
+    Ok(image)
+  }
+
+  pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), GameError> {
+    write_png(
+      path.as_ref(),
+      self.width(),
+      self.height(),
+      &self.to_rgba_bytes(),
+    )
+  }
+
+  pub fn width(&self) -> u32 {
+    self.pixels.width()
+  }
+
+  pub fn height(&self) -> u32 {
+    self.pixels.height()
+  }
+
+  pub fn size(&self) -> V2U {
+    V2U::new(self.width(), self.height())
+  }
+
+  pub fn region(&self) -> ImageRegion<'_> {
+    ImageRegion {
+      image: self,
+      pos: P2U::new(0, 0),
+      size: self.size(),
+    }
+  }
+
+  pub fn get(&self, x: i32, y: i32) -> Option<Color> {
+    self.pixels.get(x, y).copied()
+  }
+
+  pub fn get_mut(&mut self, x: i32, y: i32) -> Option<&mut Color> {
+    self.pixels.get_mut(x, y)
+  }
+
+  pub fn pixels(&self) -> &[Color] {
+    self.pixels.data()
+  }
+
+  // tightly packed RGBA bytes, row by row
+  pub fn to_rgba_bytes(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(self.pixels().len() * 4);
+    for color in self.pixels() {
+      bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+    }
+    bytes
+  }
+}
+
+// `data` is RGBA rows, e.g. from `to_rgba_bytes`
+pub(crate) fn write_png(
+  path: &Path,
+  width: u32,
+  height: u32,
+  data: &[u8],
+) -> Result<(), GameError> {
+  let file = std::fs::File::create(path).map_err(|e| GameError::io(path, e))?;
+  let w = std::io::BufWriter::new(file);
+
+  let mut encoder = png::Encoder::new(w, width, height);
+  encoder.set_color(png::ColorType::RGBA);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(data))
+    .map_err(|e| GameError::Io(path.display().to_string(), e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn encode_png(depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+    encoder.set_color(png::ColorType::RGB);
+    encoder.set_depth(depth);
+    encoder
+      .write_header()
+      .unwrap()
+      .write_image_data(data)
+      .unwrap();
+    bytes
+  }
+
+  #[test]
+  fn decodes_8_and_16_bit_pngs() {
+    let png = encode_png(png::BitDepth::Eight, &[1, 2, 3, 4, 5, 6]);
+    let image = Image::decode_png(png.as_slice()).ok().unwrap();
+    assert_eq!(image.get(1, 0), Some(Color::RGB(4, 5, 6)));
+
+    // the low bytes are dropped
+    let png = encode_png(
+      png::BitDepth::Sixteen,
+      &[1, 9, 2, 9, 3, 9, 4, 0, 5, 0, 6, 0],
+    );
+    let image = Image::decode_png(png.as_slice()).ok().unwrap();
+    assert_eq!(image.size(), V2U::new(2, 1));
+    assert_eq!(image.get(0, 0), Some(Color::RGB(1, 2, 3)));
+    assert_eq!(image.get(1, 0), Some(Color::RGB(4, 5, 6)));
+  }
+}
@@ -0,0 +1,245 @@
+use crate::error::*;
+use crate::key_status::*;
+use crate::keypad::*;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Eq, PartialEq, Hash, Clone, Debug)]
+pub struct ActionName(pub String);
+
+pub fn action(name: &str) -> ActionName {
+  ActionName(name.to_string())
+}
+
+// Named actions and the inputs bound to them, an action can have any number of bindings.
+//
+// Bindings files have one `<action> = <kind> <name>` line per binding, where kind is `key`,
+// `mouse`, `gamepad` or `keypad` and name is the sdl name of the key or button, or the
+// name of the phone key. Gamepad axes are named with the direction they are pushed in, e.g.
+//
+//   paddle_up = key Up
+//   paddle_up = keypad 2
+//   paddle_up = gamepad lefty-
+//   pause = gamepad start
+#[derive(Clone, Default)]
+pub struct InputMap {
+  bindings: HashMap<ActionName, Vec<Input>>,
+}
+
+impl InputMap {
+  pub fn new() -> InputMap {
+    InputMap {
+      bindings: HashMap::new(),
+    }
+  }
+
+  pub fn bind<I: Into<Input>>(&mut self, action: ActionName, input: I) {
+    let input = input.into();
+    let inputs = self.bindings.entry(action).or_default();
+    if !inputs.contains(&input) {
+      inputs.push(input);
+    }
+  }
+
+  pub fn unbind<I: Into<Input>>(&mut self, action: &ActionName, input: I) {
+    let input = input.into();
+    if let Some(inputs) = self.bindings.get_mut(action) {
+      inputs.retain(|i| *i != input);
+    }
+  }
+
+  pub fn set_bindings(&mut self, action: ActionName, inputs: Vec<Input>) {
+    self.bindings.insert(action, inputs);
+  }
+
+  pub fn bindings(&self, action: &ActionName) -> &[Input] {
+    self
+      .bindings
+      .get(action)
+      .map(|inputs| inputs.as_slice())
+      .unwrap_or(&[])
+  }
+
+  // Actions that appear in the file get its bindings, all others keep theirs. A missing file
+  // is not an error, so defaults can be bound first and then overridden.
+  pub fn load_bindings<P: AsRef<Path>>(&mut self, path: P) -> Result<(), GameError> {
+    let path = path.as_ref();
+    let text = match std::fs::read_to_string(path) {
+      Ok(text) => text,
+      Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+      Err(e) => return Err(GameError::io(path, e)),
+    };
+    let mut loaded: HashMap<ActionName, Vec<Input>> = HashMap::new();
+    for (line_number, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let input = line
+        .split_once('=')
+        .and_then(|(name, input)| Some((action(name.trim()), parse_input(input.trim())?)));
+      match input {
+        Some((action, input)) => loaded.entry(action).or_default().push(input),
+        None => {
+          return Err(GameError::invalid_file(
+            path,
+            format!("invalid binding on line {}: {}", line_number + 1, line),
+          ))
+        }
+      }
+    }
+    self.bindings.extend(loaded);
+    Ok(())
+  }
+
+  pub fn save_bindings<P: AsRef<Path>>(&self, path: P) -> Result<(), GameError> {
+    let path = path.as_ref();
+    let mut actions: Vec<&ActionName> = self.bindings.keys().collect();
+    actions.sort_by(|a, b| a.0.cmp(&b.0));
+    let write = || {
+      let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+      for action in actions {
+        for input in self.bindings(action) {
+          writeln!(writer, "{} = {}", action.0, format_input(input))?;
+        }
+      }
+      writer.flush()
+    };
+    write().map_err(|e| GameError::io(path, e))
+  }
+}
+
+// The pressed and just pressed queries of `KeyStatus`, but for actions. An action is pressed
+// while any of its inputs is.
+pub struct Actions<'a> {
+  key_status: &'a KeyStatus,
+  input_map: &'a InputMap,
+}
+
+impl<'a> Actions<'a> {
+  pub fn new(key_status: &'a KeyStatus, input_map: &'a InputMap) -> Actions<'a> {
+    Actions {
+      key_status,
+      input_map,
+    }
+  }
+
+  pub fn is_pressed(&self, action: &ActionName) -> bool {
+    self.any(action, |input| self.key_status.is_key_pressed(input))
+  }
+
+  pub fn was_just_pressed(&self, action: &ActionName) -> bool {
+    self.any(action, |input| self.key_status.was_just_pressed(input))
+  }
+
+  // released this tick, and no other input of the action is still held
+  pub fn was_just_released(&self, action: &ActionName) -> bool {
+    self.any(action, |input| self.key_status.was_just_released(input)) && !self.is_pressed(action)
+  }
+
+  pub fn held_ticks(&self, action: &ActionName) -> u32 {
+    self
+      .input_map
+      .bindings(action)
+      .iter()
+      .map(|&input| self.key_status.held_ticks(input))
+      .max()
+      .unwrap_or(0)
+  }
+
+  pub fn was_triggered(&self, action: &ActionName) -> bool {
+    self.any(action, |input| self.key_status.was_triggered(input))
+  }
+
+  fn any<F: Fn(Input) -> bool>(&self, action: &ActionName, f: F) -> bool {
+    self
+      .input_map
+      .bindings(action)
+      .iter()
+      .any(|&input| f(input))
+  }
+}
+
+fn parse_input(text: &str) -> Option<Input> {
+  let (kind, name) = text.split_once(' ')?;
+  let name = name.trim();
+  match kind {
+    "key" => Keycode::from_name(name).map(Input::Key),
+    "mouse" => {
+      let button = match name {
+        "left" => MouseButton::Left,
+        "middle" => MouseButton::Middle,
+        "right" => MouseButton::Right,
+        "x1" => MouseButton::X1,
+        "x2" => MouseButton::X2,
+        // written for buttons SDL doesn't know, so saved bindings always load again
+        "unknown" => MouseButton::Unknown,
+        _ => return None,
+      };
+      Some(Input::Mouse(button))
+    }
+    "gamepad" => match name.strip_suffix('-').or_else(|| name.strip_suffix('+')) {
+      Some(axis) => {
+        let direction = if name.ends_with('-') {
+          AxisDirection::Negative
+        } else {
+          AxisDirection::Positive
+        };
+        sdl2::controller::Axis::from_string(axis).map(|axis| Input::GamepadAxis(axis, direction))
+      }
+      None => sdl2::controller::Button::from_string(name).map(Input::Gamepad),
+    },
+    "keypad" => PhoneKey::from_name(name).map(Input::Keypad),
+    _ => None,
+  }
+}
+
+fn format_input(input: &Input) -> String {
+  match *input {
+    Input::Key(keycode) => format!("key {}", keycode.name()),
+    Input::Mouse(button) => {
+      let name = match button {
+        MouseButton::Left => "left",
+        MouseButton::Middle => "middle",
+        MouseButton::Right => "right",
+        MouseButton::X1 => "x1",
+        MouseButton::X2 => "x2",
+        MouseButton::Unknown => "unknown",
+      };
+      format!("mouse {}", name)
+    }
+    Input::Gamepad(button) => format!("gamepad {}", button.string()),
+    Input::GamepadAxis(axis, AxisDirection::Negative) => format!("gamepad {}-", axis.string()),
+    Input::GamepadAxis(axis, AxisDirection::Positive) => format!("gamepad {}+", axis.string()),
+    Input::Keypad(key) => format!("keypad {}", key.name()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sdl2::controller::{Axis, Button};
+
+  #[test]
+  fn formats_inputs_it_can_parse() {
+    let inputs = [
+      Input::Key(Keycode::Up),
+      Input::Key(Keycode::Space),
+      Input::Mouse(MouseButton::Left),
+      Input::Mouse(MouseButton::Unknown),
+      Input::Gamepad(Button::DPadUp),
+      Input::GamepadAxis(Axis::LeftY, AxisDirection::Negative),
+      Input::GamepadAxis(Axis::TriggerRight, AxisDirection::Positive),
+      Input::Keypad(PhoneKey::Num5),
+    ];
+    for input in &inputs {
+      let text = format_input(input);
+      assert_eq!(parse_input(&text), Some(*input), "{}", text);
+    }
+    assert_eq!(format_input(&inputs[5]), "gamepad lefty-");
+    assert_eq!(parse_input("mouse thumb"), None);
+  }
+}
@@ -0,0 +1,302 @@
+// a small JSON reader, enough for the metadata files exported by art tools
+//
+// Object members keep their order, as some exporters list frames in an object.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  // None if this is not an object or has no such member
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_f64(&self) -> Option<f64> {
+    match self {
+      Json::Number(n) => Some(*n),
+      _ => None,
+    }
+  }
+
+  pub fn as_u32(&self) -> Option<u32> {
+    self
+      .as_f64()
+      .filter(|n| *n >= 0.0 && n.fract() == 0.0 && *n <= u32::MAX as f64)
+      .map(|n| n as u32)
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Json::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(items) => Some(items),
+      _ => None,
+    }
+  }
+
+  pub fn as_object(&self) -> Option<&[(String, Json)]> {
+    match self {
+      Json::Object(members) => Some(members),
+      _ => None,
+    }
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct JsonError {
+  // in bytes
+  pub offset: usize,
+  pub expected: &'static str,
+}
+
+impl std::fmt::Display for JsonError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "expected {} at byte {}", self.expected, self.offset)
+  }
+}
+
+impl std::error::Error for JsonError {}
+
+pub fn parse_json(text: &str) -> Result<Json, JsonError> {
+  let mut parser = Parser { text, offset: 0 };
+  let value = parser.value()?;
+  parser.skip_whitespace();
+  if parser.offset < text.len() {
+    return Err(parser.error("end of input"));
+  }
+  Ok(value)
+}
+
+struct Parser<'t> {
+  text: &'t str,
+  offset: usize,
+}
+
+impl<'t> Parser<'t> {
+  fn error(&self, expected: &'static str) -> JsonError {
+    JsonError {
+      offset: self.offset,
+      expected,
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.text[self.offset..].chars().next()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let ch = self.peek()?;
+    self.offset += ch.len_utf8();
+    Some(ch)
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+      self.offset += 1;
+    }
+  }
+
+  fn expect(&mut self, ch: char, expected: &'static str) -> Result<(), JsonError> {
+    self.skip_whitespace();
+    if self.peek() == Some(ch) {
+      self.offset += 1;
+      Ok(())
+    } else {
+      Err(self.error(expected))
+    }
+  }
+
+  fn keyword(&mut self, keyword: &str, value: Json) -> Result<Json, JsonError> {
+    if self.text[self.offset..].starts_with(keyword) {
+      self.offset += keyword.len();
+      Ok(value)
+    } else {
+      Err(self.error("a value"))
+    }
+  }
+
+  fn value(&mut self) -> Result<Json, JsonError> {
+    self.skip_whitespace();
+    match self.peek() {
+      Some('{') => self.object(),
+      Some('[') => self.array(),
+      Some('"') => Ok(Json::String(self.string()?)),
+      Some('t') => self.keyword("true", Json::Bool(true)),
+      Some('f') => self.keyword("false", Json::Bool(false)),
+      Some('n') => self.keyword("null", Json::Null),
+      Some('-' | '0'..='9') => self.number(),
+      _ => Err(self.error("a value")),
+    }
+  }
+
+  fn object(&mut self) -> Result<Json, JsonError> {
+    self.expect('{', "'{'")?;
+    let mut members = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some('}') {
+      self.offset += 1;
+      return Ok(Json::Object(members));
+    }
+    loop {
+      self.skip_whitespace();
+      let key = self.string()?;
+      self.expect(':', "':'")?;
+      members.push((key, self.value()?));
+      self.skip_whitespace();
+      match self.next() {
+        Some(',') => {}
+        Some('}') => return Ok(Json::Object(members)),
+        _ => return Err(self.error("',' or '}'")),
+      }
+    }
+  }
+
+  fn array(&mut self) -> Result<Json, JsonError> {
+    self.expect('[', "'['")?;
+    let mut items = Vec::new();
+    self.skip_whitespace();
+    if self.peek() == Some(']') {
+      self.offset += 1;
+      return Ok(Json::Array(items));
+    }
+    loop {
+      items.push(self.value()?);
+      self.skip_whitespace();
+      match self.next() {
+        Some(',') => {}
+        Some(']') => return Ok(Json::Array(items)),
+        _ => return Err(self.error("',' or ']'")),
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, JsonError> {
+    self.expect('"', "a string")?;
+    let mut s = String::new();
+    loop {
+      match self.next().ok_or_else(|| self.error("'\"'"))? {
+        '"' => return Ok(s),
+        '\\' => match self.next() {
+          Some('"') => s.push('"'),
+          Some('\\') => s.push('\\'),
+          Some('/') => s.push('/'),
+          Some('b') => s.push('\u{8}'),
+          Some('f') => s.push('\u{c}'),
+          Some('n') => s.push('\n'),
+          Some('r') => s.push('\r'),
+          Some('t') => s.push('\t'),
+          Some('u') => s.push(self.unicode_escape()?),
+          _ => return Err(self.error("an escape")),
+        },
+        ch => s.push(ch),
+      }
+    }
+  }
+
+  // after `\u`, surrogate pairs are combined
+  fn unicode_escape(&mut self) -> Result<char, JsonError> {
+    let high = self.hex4()?;
+    if !(0xd800..0xdc00).contains(&high) {
+      return Ok(std::char::from_u32(high).unwrap_or(std::char::REPLACEMENT_CHARACTER));
+    }
+    if !self.text[self.offset..].starts_with("\\u") {
+      return Ok(std::char::REPLACEMENT_CHARACTER);
+    }
+    self.offset += 2;
+    let low = self.hex4()?;
+    let code = 0x10000 + ((high - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+    Ok(std::char::from_u32(code).unwrap_or(std::char::REPLACEMENT_CHARACTER))
+  }
+
+  fn hex4(&mut self) -> Result<u32, JsonError> {
+    let digits = self
+      .text
+      .get(self.offset..self.offset + 4)
+      .ok_or_else(|| self.error("4 hex digits"))?;
+    let code = u32::from_str_radix(digits, 16).map_err(|_| self.error("4 hex digits"))?;
+    self.offset += 4;
+    Ok(code)
+  }
+
+  fn number(&mut self) -> Result<Json, JsonError> {
+    let start = self.offset;
+    while let Some('-' | '+' | '.' | 'e' | 'E' | '0'..='9') = self.peek() {
+      self.offset += 1;
+    }
+    self.text[start..self.offset]
+      .parse()
+      .map(Json::Number)
+      .map_err(|_| JsonError {
+        offset: start,
+        expected: "a number",
+      })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_values() {
+    let text = r#" {"a": [1, -2.5e1, true, null], "b": {"c": "x\"\u00e9\ud83d\ude00"}} "#;
+    let json = parse_json(text).unwrap();
+    assert_eq!(
+      json.get("a"),
+      Some(&Json::Array(vec![
+        Json::Number(1.0),
+        Json::Number(-25.0),
+        Json::Bool(true),
+        Json::Null
+      ]))
+    );
+    let c = json.get("b").and_then(|b| b.get("c"));
+    assert_eq!(c.and_then(Json::as_str), Some("x\"\u{e9}\u{1f600}"));
+  }
+
+  #[test]
+  fn reports_errors() {
+    assert_eq!(
+      parse_json("[1, 2"),
+      Err(JsonError {
+        offset: 5,
+        expected: "',' or ']'"
+      })
+    );
+    assert_eq!(
+      parse_json("{\"a\" 1}"),
+      Err(JsonError {
+        offset: 5,
+        expected: "':'"
+      })
+    );
+    assert_eq!(
+      parse_json("[] x"),
+      Err(JsonError {
+        offset: 3,
+        expected: "end of input"
+      })
+    );
+  }
+}
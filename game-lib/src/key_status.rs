@@ -1,25 +1,267 @@
-use std::collections::HashSet;
+use crate::keypad::PhoneKey;
+use crate::replay::InputEvent;
+use crate::types::*;
+use sdl2::controller::{Axis, Button};
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 
+// anything that can be held down
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Input {
+  Key(Keycode),
+  Mouse(MouseButton),
+  Gamepad(Button),
+  GamepadAxis(Axis, AxisDirection),
+  Keypad(PhoneKey),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum AxisDirection {
+  Negative,
+  Positive,
+}
+
+impl From<Keycode> for Input {
+  fn from(keycode: Keycode) -> Input {
+    Input::Key(keycode)
+  }
+}
+
+impl From<MouseButton> for Input {
+  fn from(button: MouseButton) -> Input {
+    Input::Mouse(button)
+  }
+}
+
+impl From<Button> for Input {
+  fn from(button: Button) -> Input {
+    Input::Gamepad(button)
+  }
+}
+
+// Tracks keys, mouse, gamepad and phone buttons, the queries take any of them. Transitions
+// are kept until the end of the next game tick, so a key that is pressed and released
+// between two ticks still shows up as just pressed and just released.
+//
+// Gamepad axes are also buttons, pressed while the stick is pushed past the deadzone in
+// their direction. The inputs of all connected gamepads are merged.
 pub struct KeyStatus {
-  key_status: HashSet<sdl2::keyboard::Keycode>,
+  key_status: HashMap<Input, u32>,
+  just_pressed: HashSet<Input>,
+  just_released: HashSet<Input>,
+  axes: HashMap<Axis, i16>,
+  axis_deadzone: f32,
+  pointer: Option<P2I>,
+  tap: Option<P2I>,
+  auto_repeat: Option<AutoRepeat>,
+}
+
+// the longest press that still counts as a tap
+const TAP_TICKS: u32 = 5;
+
+// both in game ticks
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AutoRepeat {
+  pub delay: u32,
+  pub interval: u32,
 }
 
 impl KeyStatus {
   pub fn new() -> KeyStatus {
     KeyStatus {
-      key_status: HashSet::new(),
+      key_status: HashMap::new(),
+      just_pressed: HashSet::new(),
+      just_released: HashSet::new(),
+      auto_repeat: None,
+      axes: HashMap::new(),
+      axis_deadzone: 0.25,
+      pointer: None,
+      tap: None,
     }
   }
 
-  pub fn set_key_pressed(&mut self, keycode: sdl2::keyboard::Keycode, pressed: bool) {
+  // the part of the axis range around the center that counts as 0, from 0 to 1
+  pub fn set_axis_deadzone(&mut self, axis_deadzone: f32) {
+    self.axis_deadzone = axis_deadzone;
+  }
+
+  pub fn set_auto_repeat(&mut self, auto_repeat: Option<AutoRepeat>) {
+    self.auto_repeat = auto_repeat;
+  }
+
+  pub fn set_key_pressed<I: Into<Input>>(&mut self, input: I, pressed: bool) {
+    let input = input.into();
     if pressed {
-      self.key_status.insert(keycode);
+      if let Entry::Vacant(entry) = self.key_status.entry(input) {
+        entry.insert(1);
+        self.just_pressed.insert(input);
+      }
+    } else if let Some(held_ticks) = self.key_status.remove(&input) {
+      self.just_released.insert(input);
+      if input == Input::Mouse(MouseButton::Left) && held_ticks <= TAP_TICKS {
+        self.tap = self.pointer;
+      }
+    }
+  }
+
+  pub fn apply(&mut self, input_event: &InputEvent) {
+    match *input_event {
+      InputEvent::KeyDown { keycode, .. } => self.set_key_pressed(keycode, true),
+      InputEvent::KeyUp { keycode } => self.set_key_pressed(keycode, false),
+      InputEvent::MouseButtonDown { button } => self.set_key_pressed(button, true),
+      InputEvent::MouseButtonUp { button } => self.set_key_pressed(button, false),
+      InputEvent::PhoneKeyDown { key } => self.set_key_pressed(key, true),
+      InputEvent::PhoneKeyUp { key } => self.set_key_pressed(key, false),
+      InputEvent::GamepadButtonDown { button } => self.set_key_pressed(button, true),
+      InputEvent::GamepadButtonUp { button } => self.set_key_pressed(button, false),
+      InputEvent::GamepadAxis { axis, value } => {
+        self.axes.insert(axis, value);
+        let position = self.axis(axis);
+        self.set_key_pressed(
+          Input::GamepadAxis(axis, AxisDirection::Negative),
+          position < 0.0,
+        );
+        self.set_key_pressed(
+          Input::GamepadAxis(axis, AxisDirection::Positive),
+          position > 0.0,
+        );
+      }
+      InputEvent::PointerMotion { x, y } => self.pointer = Some(P2I::new(x, y)),
+    }
+  }
+
+  // called after every game tick
+  pub fn end_tick(&mut self) {
+    self.just_pressed.clear();
+    self.just_released.clear();
+    self.tap = None;
+    for held_ticks in self.key_status.values_mut() {
+      *held_ticks += 1;
+    }
+  }
+
+  // from -1 to 1, 0 inside the deadzone
+  pub fn axis(&self, axis: Axis) -> f32 {
+    let value = self.axes.get(&axis).copied().unwrap_or(0);
+    let position = (value as f32 / i16::MAX as f32).max(-1.0);
+    if position.abs() <= self.axis_deadzone {
+      0.0
     } else {
-      self.key_status.remove(&keycode);
+      position.signum() * (position.abs() - self.axis_deadzone) / (1.0 - self.axis_deadzone)
+    }
+  }
+
+  // The mouse or finger position in screen pixels, it can be outside of the screen. Touches
+  // press the left mouse button.
+  pub fn pointer(&self) -> Option<P2I> {
+    self.pointer
+  }
+
+  // where the left mouse button or a finger was released this tick after a short press
+  pub fn tap(&self) -> Option<P2I> {
+    self.tap
+  }
+
+  pub fn is_key_pressed<I: Into<Input>>(&self, input: I) -> bool {
+    self.key_status.contains_key(&input.into())
+  }
+
+  pub fn was_just_pressed<I: Into<Input>>(&self, input: I) -> bool {
+    self.just_pressed.contains(&input.into())
+  }
+
+  pub fn was_any_just_pressed(&self) -> bool {
+    !self.just_pressed.is_empty()
+  }
+
+  pub fn was_just_released<I: Into<Input>>(&self, input: I) -> bool {
+    self.just_released.contains(&input.into())
+  }
+
+  // the number of ticks the input has been held, including the current one, 0 if it is up
+  pub fn held_ticks<I: Into<Input>>(&self, input: I) -> u32 {
+    self.key_status.get(&input.into()).copied().unwrap_or(0)
+  }
+
+  // just pressed, or held long enough for the auto repeat to fire this tick
+  pub fn was_triggered<I: Into<Input>>(&self, input: I) -> bool {
+    let input = input.into();
+    if self.was_just_pressed(input) {
+      return true;
+    }
+    let held_ticks = self.held_ticks(input);
+    match self.auto_repeat {
+      Some(AutoRepeat { delay, interval }) if held_ticks > delay => {
+        (held_ticks - delay - 1).is_multiple_of(interval.max(1))
+      }
+      _ => false,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_transitions_until_the_end_of_the_tick() {
+    let mut key_status = KeyStatus::new();
+    key_status.set_key_pressed(Keycode::Up, true);
+    key_status.set_key_pressed(Keycode::Up, false);
+    assert!(!key_status.is_key_pressed(Keycode::Up));
+    assert!(key_status.was_just_pressed(Keycode::Up));
+    assert!(key_status.was_just_released(Keycode::Up));
+    key_status.end_tick();
+    assert!(!key_status.was_just_pressed(Keycode::Up));
+    assert!(!key_status.was_just_released(Keycode::Up));
+  }
+
+  #[test]
+  fn counts_held_ticks() {
+    let mut key_status = KeyStatus::new();
+    assert_eq!(key_status.held_ticks(Keycode::Up), 0);
+    key_status.set_key_pressed(Keycode::Up, true);
+    assert_eq!(key_status.held_ticks(Keycode::Up), 1);
+    key_status.end_tick();
+    key_status.end_tick();
+    assert_eq!(key_status.held_ticks(Keycode::Up), 3);
+    // pressing again while held changes nothing
+    key_status.set_key_pressed(Keycode::Up, true);
+    assert_eq!(key_status.held_ticks(Keycode::Up), 3);
+    key_status.set_key_pressed(Keycode::Up, false);
+    assert_eq!(key_status.held_ticks(Keycode::Up), 0);
+  }
+
+  // the ticks from 1 to `ticks` on which the held key triggers
+  fn triggered_ticks(auto_repeat: Option<AutoRepeat>, ticks: u32) -> Vec<u32> {
+    let mut key_status = KeyStatus::new();
+    key_status.set_auto_repeat(auto_repeat);
+    key_status.set_key_pressed(Keycode::Up, true);
+    let mut triggered = Vec::new();
+    for tick in 1..=ticks {
+      if key_status.was_triggered(Keycode::Up) {
+        triggered.push(tick);
+      }
+      key_status.end_tick();
     }
+    triggered
   }
 
-  pub fn is_key_pressed(&self, keycode: sdl2::keyboard::Keycode) -> bool {
-    self.key_status.contains(&keycode)
+  #[test]
+  fn auto_repeats() {
+    assert_eq!(triggered_ticks(None, 10), vec![1]);
+    let auto_repeat = AutoRepeat {
+      delay: 3,
+      interval: 2,
+    };
+    assert_eq!(triggered_ticks(Some(auto_repeat), 10), vec![1, 4, 6, 8, 10]);
+    // every tick after the delay
+    let auto_repeat = AutoRepeat {
+      delay: 3,
+      interval: 0,
+    };
+    assert_eq!(triggered_ticks(Some(auto_repeat), 6), vec![1, 4, 5, 6]);
   }
 }
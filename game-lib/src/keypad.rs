@@ -0,0 +1,351 @@
+// Emulation of a 12-key phone keypad
+//
+// Phone keys are inputs like any other, they are pressed by their bound keyboard keys or by
+// clicking the optional on-screen keypad below the lcd. Games written only against
+// `PhoneKey`s play the same either way.
+use crate::assets::*;
+use crate::color::*;
+use crate::image::*;
+use crate::key_status::*;
+use crate::renderer::*;
+use crate::replay::InputEvent;
+use crate::types::*;
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PhoneKey {
+  Num0,
+  Num1,
+  Num2,
+  Num3,
+  Num4,
+  Num5,
+  Num6,
+  Num7,
+  Num8,
+  Num9,
+  Star,
+  Hash,
+  SoftLeft,
+  SoftRight,
+  Call,
+  HangUp,
+}
+
+pub const PHONE_KEYS: [PhoneKey; 16] = [
+  PhoneKey::Num0,
+  PhoneKey::Num1,
+  PhoneKey::Num2,
+  PhoneKey::Num3,
+  PhoneKey::Num4,
+  PhoneKey::Num5,
+  PhoneKey::Num6,
+  PhoneKey::Num7,
+  PhoneKey::Num8,
+  PhoneKey::Num9,
+  PhoneKey::Star,
+  PhoneKey::Hash,
+  PhoneKey::SoftLeft,
+  PhoneKey::SoftRight,
+  PhoneKey::Call,
+  PhoneKey::HangUp,
+];
+
+impl PhoneKey {
+  // used in bindings files and replays
+  pub fn name(self) -> &'static str {
+    match self {
+      PhoneKey::Num0 => "0",
+      PhoneKey::Num1 => "1",
+      PhoneKey::Num2 => "2",
+      PhoneKey::Num3 => "3",
+      PhoneKey::Num4 => "4",
+      PhoneKey::Num5 => "5",
+      PhoneKey::Num6 => "6",
+      PhoneKey::Num7 => "7",
+      PhoneKey::Num8 => "8",
+      PhoneKey::Num9 => "9",
+      PhoneKey::Star => "star",
+      PhoneKey::Hash => "hash",
+      PhoneKey::SoftLeft => "soft_left",
+      PhoneKey::SoftRight => "soft_right",
+      PhoneKey::Call => "call",
+      PhoneKey::HangUp => "hang_up",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<PhoneKey> {
+    PHONE_KEYS.iter().copied().find(|key| key.name() == name)
+  }
+
+  pub fn index(self) -> u8 {
+    PHONE_KEYS.iter().position(|&key| key == self).unwrap() as u8
+  }
+
+  pub fn from_index(index: u8) -> Option<PhoneKey> {
+    PHONE_KEYS.get(index as usize).copied()
+  }
+
+  fn label(self) -> &'static str {
+    match self {
+      PhoneKey::SoftLeft => "sl",
+      PhoneKey::SoftRight => "sr",
+      PhoneKey::HangUp => "end",
+      key => key.name(),
+    }
+  }
+}
+
+impl From<PhoneKey> for Input {
+  fn from(key: PhoneKey) -> Input {
+    Input::Keypad(key)
+  }
+}
+
+// the on-screen keypad, soft keys and call keys above the number keys
+const LAYOUT: [[Option<PhoneKey>; 3]; 6] = [
+  [Some(PhoneKey::SoftLeft), None, Some(PhoneKey::SoftRight)],
+  [Some(PhoneKey::Call), None, Some(PhoneKey::HangUp)],
+  [
+    Some(PhoneKey::Num1),
+    Some(PhoneKey::Num2),
+    Some(PhoneKey::Num3),
+  ],
+  [
+    Some(PhoneKey::Num4),
+    Some(PhoneKey::Num5),
+    Some(PhoneKey::Num6),
+  ],
+  [
+    Some(PhoneKey::Num7),
+    Some(PhoneKey::Num8),
+    Some(PhoneKey::Num9),
+  ],
+  [
+    Some(PhoneKey::Star),
+    Some(PhoneKey::Num0),
+    Some(PhoneKey::Hash),
+  ],
+];
+
+pub struct Keypad {
+  bindings: HashMap<Keycode, PhoneKey>,
+  // the key held down with the mouse on the on-screen keypad
+  clicked: Option<PhoneKey>,
+}
+
+impl Keypad {
+  // numpad and number row for the digits, F1/F2 for the soft keys, enter and backspace for
+  // call and hang up
+  pub fn new() -> Keypad {
+    let mut keypad = Keypad {
+      bindings: HashMap::new(),
+      clicked: None,
+    };
+    let digits = [
+      (Keycode::Num0, Keycode::Kp0, PhoneKey::Num0),
+      (Keycode::Num1, Keycode::Kp1, PhoneKey::Num1),
+      (Keycode::Num2, Keycode::Kp2, PhoneKey::Num2),
+      (Keycode::Num3, Keycode::Kp3, PhoneKey::Num3),
+      (Keycode::Num4, Keycode::Kp4, PhoneKey::Num4),
+      (Keycode::Num5, Keycode::Kp5, PhoneKey::Num5),
+      (Keycode::Num6, Keycode::Kp6, PhoneKey::Num6),
+      (Keycode::Num7, Keycode::Kp7, PhoneKey::Num7),
+      (Keycode::Num8, Keycode::Kp8, PhoneKey::Num8),
+      (Keycode::Num9, Keycode::Kp9, PhoneKey::Num9),
+    ];
+    for &(row_keycode, numpad_keycode, key) in &digits {
+      keypad.bind(row_keycode, key);
+      keypad.bind(numpad_keycode, key);
+    }
+    keypad.bind(Keycode::KpMultiply, PhoneKey::Star);
+    keypad.bind(Keycode::Asterisk, PhoneKey::Star);
+    keypad.bind(Keycode::KpHash, PhoneKey::Hash);
+    keypad.bind(Keycode::Hash, PhoneKey::Hash);
+    keypad.bind(Keycode::KpDivide, PhoneKey::Hash);
+    keypad.bind(Keycode::F1, PhoneKey::SoftLeft);
+    keypad.bind(Keycode::F2, PhoneKey::SoftRight);
+    keypad.bind(Keycode::Return, PhoneKey::Call);
+    keypad.bind(Keycode::KpEnter, PhoneKey::Call);
+    keypad.bind(Keycode::Backspace, PhoneKey::HangUp);
+    keypad
+  }
+
+  pub fn bind(&mut self, keycode: Keycode, key: PhoneKey) {
+    self.bindings.insert(keycode, key);
+  }
+
+  pub fn unbind(&mut self, keycode: Keycode) {
+    self.bindings.remove(&keycode);
+  }
+
+  // the phone key event caused by a keyboard event
+  pub fn translate(&self, input_event: &InputEvent) -> Option<InputEvent> {
+    match *input_event {
+      InputEvent::KeyDown { keycode, .. } => self
+        .bindings
+        .get(&keycode)
+        .map(|&key| InputEvent::PhoneKeyDown { key }),
+      InputEvent::KeyUp { keycode } => self
+        .bindings
+        .get(&keycode)
+        .map(|&key| InputEvent::PhoneKeyUp { key }),
+      _ => None,
+    }
+  }
+
+  // turns clicks on the on-screen keypad at `area` of the window into phone key events
+  pub fn on_screen_event(&mut self, event: &Event, area: sdl2::rect::Rect) -> Option<InputEvent> {
+    match *event {
+      Event::MouseButtonDown {
+        mouse_btn: sdl2::mouse::MouseButton::Left,
+        x,
+        y,
+        ..
+      } if area.contains_point((x, y)) => {
+        let column = (x - area.x()) as usize * 3 / area.width() as usize;
+        let row = (y - area.y()) as usize * LAYOUT.len() / area.height() as usize;
+        let key = LAYOUT[row][column]?;
+        self.clicked = Some(key);
+        Some(InputEvent::PhoneKeyDown { key })
+      }
+      Event::MouseButtonUp {
+        mouse_btn: sdl2::mouse::MouseButton::Left,
+        ..
+      } => self
+        .clicked
+        .take()
+        .map(|key| InputEvent::PhoneKeyUp { key }),
+      _ => None,
+    }
+  }
+
+  // the on-screen keypad at lcd resolution, pressed keys are shaded
+  pub fn render(
+    &self,
+    assets: &Assets,
+    key_status: &KeyStatus,
+    size: V2U,
+    font_color: Color,
+    background_color: Color,
+  ) -> Image {
+    let mut image = Image::filled(size.x, size.y, background_color);
+    let mut frame = Frame {
+      target: &mut image,
+      assets,
+    };
+    let key_w = size.x / 3;
+    let key_h = size.y / LAYOUT.len() as u32;
+    for (row, keys) in LAYOUT.iter().enumerate() {
+      for (column, key) in keys.iter().enumerate() {
+        let key = match key {
+          Some(key) => *key,
+          None => continue,
+        };
+        let x = (column as u32 * key_w) as i32;
+        let y = (row as u32 * key_h) as i32;
+        let fill_color = if key_status.is_key_pressed(key) {
+          font_color.lerp(background_color, 0.5)
+        } else {
+          background_color
+        };
+        frame.draw_rect(x + 1, y, key_w - 2, key_h - 1, font_color);
+        frame.draw_rect(x + 2, y + 1, key_w - 4, key_h - 3, fill_color);
+        let label = key.label();
+        let label_w = assets.default_font().text_width(label) - 1;
+        frame.draw_text(
+          x + (key_w as i32 - label_w as i32) / 2,
+          y + (key_h as i32 - assets.default_font().line_height() as i32) / 2,
+          label,
+        );
+      }
+    }
+    image
+  }
+}
+
+impl Default for Keypad {
+  fn default() -> Keypad {
+    Keypad::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use sdl2::mouse::MouseButton;
+
+  #[test]
+  fn number_row_and_numpad_press_the_same_key() {
+    let keypad = Keypad::new();
+    let key_down = |keycode| {
+      keypad.translate(&InputEvent::KeyDown {
+        keycode,
+        repeat: false,
+      })
+    };
+    for &(row_keycode, numpad_keycode) in
+      &[(Keycode::Num0, Keycode::Kp0), (Keycode::Num7, Keycode::Kp7)]
+    {
+      assert_eq!(key_down(row_keycode), key_down(numpad_keycode));
+    }
+    assert_eq!(
+      key_down(Keycode::Kp5),
+      Some(InputEvent::PhoneKeyDown {
+        key: PhoneKey::Num5
+      })
+    );
+    assert_eq!(
+      keypad.translate(&InputEvent::KeyUp {
+        keycode: Keycode::Num5
+      }),
+      Some(InputEvent::PhoneKeyUp {
+        key: PhoneKey::Num5
+      })
+    );
+  }
+
+  #[test]
+  fn clicks_press_on_screen_keys() {
+    let mut keypad = Keypad::new();
+    // 3 columns of 30 and 6 rows of 10 pixels
+    let area = sdl2::rect::Rect::new(0, 100, 90, 60);
+    let down = |x, y| Event::MouseButtonDown {
+      timestamp: 0,
+      window_id: 0,
+      which: 0,
+      mouse_btn: MouseButton::Left,
+      clicks: 1,
+      x,
+      y,
+    };
+    let up = || {
+      InputEvent::MouseButtonUp {
+        button: MouseButton::Left,
+      }
+      .to_event()
+      .unwrap()
+    };
+
+    assert_eq!(
+      keypad.on_screen_event(&down(45, 135), area),
+      Some(InputEvent::PhoneKeyDown {
+        key: PhoneKey::Num5
+      })
+    );
+    assert_eq!(
+      keypad.on_screen_event(&up(), area),
+      Some(InputEvent::PhoneKeyUp {
+        key: PhoneKey::Num5
+      })
+    );
+    assert_eq!(keypad.on_screen_event(&up(), area), None);
+
+    // the gap between the soft keys and clicks outside the keypad
+    assert_eq!(keypad.on_screen_event(&down(45, 105), area), None);
+    assert_eq!(keypad.on_screen_event(&down(45, 50), area), None);
+    assert_eq!(keypad.on_screen_event(&up(), area), None);
+  }
+}
@@ -1,15 +1,60 @@
 pub mod run;
 pub use run::*;
+pub mod game_state;
+pub use game_state::*;
+pub mod scene;
+pub use scene::*;
 pub mod gcontext;
 pub use gcontext::*;
+pub mod error;
+pub use error::*;
 pub mod config;
 pub use config::*;
+pub mod color;
+pub use color::*;
+pub mod image;
+pub use image::*;
+pub mod ascii_surface;
+pub use ascii_surface::*;
+pub mod store;
+pub use store::*;
+pub mod pack;
+pub use pack::*;
+pub mod assets;
+pub use assets::*;
+pub mod font;
+pub use font::*;
+pub mod paragraph;
+pub use paragraph::*;
+pub mod animation;
+pub use animation::*;
+pub mod transform;
+pub use transform::*;
+pub mod json;
+pub use json::*;
+pub mod sheet_metadata;
+pub use sheet_metadata::*;
+pub mod renderer;
+pub use renderer::*;
+pub mod golden;
 
 pub mod types;
 pub use types::*;
 
 pub mod key_status;
 pub use key_status::*;
+pub mod input_map;
+pub use input_map::*;
+pub mod keypad;
+pub use keypad::*;
+pub mod beeper;
+pub use beeper::*;
+pub mod rtttl;
+pub use rtttl::*;
+pub mod replay;
+pub use replay::*;
+pub mod rng;
+pub use rng::*;
 pub mod incmap;
 pub use incmap::*;
 pub mod math_utils;
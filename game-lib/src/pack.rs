@@ -0,0 +1,356 @@
+// asset packs
+//
+// A pack is a tar archive, or a directory laid out the same, with a `manifest.txt` listing the
+// assets in it, a line per asset with its kind, name and file, e.g.
+//
+//   surface ball ball.txt
+//   sprite_sheet items items.png 4x2
+//   sprite_sheet hero hero.json
+//   font small small.bdf
+//   font big big.png 6x8 abcdefghijklmnopqrstuvwxyz
+//   sfx title title.rtttl
+//
+// Sprite sheets are a grid of the given number of cells or come with Aseprite or TexturePacker
+// metadata, fonts are BDF files or glyph sheets with a cell size and then their characters in
+// order, after a single space. Lines starting with '#' are comments. Games can embed a pack
+// with `include_bytes!`, so the binary runs from any directory and the web build needs no
+// separate files.
+use crate::types::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "manifest.txt";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PackError {
+  // the path and what went wrong
+  Io(String, String),
+  InvalidArchive(&'static str),
+  MissingFile(String),
+  InvalidManifestLine(usize, String),
+  // a file in the pack that failed to parse or decode, and why
+  InvalidFile(String, String),
+}
+
+impl std::fmt::Display for PackError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      PackError::Io(path, e) => write!(f, "can't read {}: {}", path, e),
+      PackError::InvalidArchive(e) => write!(f, "invalid asset pack: {}", e),
+      PackError::MissingFile(file) => write!(f, "{} is missing from the asset pack", file),
+      PackError::InvalidManifestLine(line, text) => {
+        write!(f, "invalid {} line {}: {}", MANIFEST_FILE, line, text)
+      }
+      PackError::InvalidFile(file, e) => write!(f, "{}: {}", file, e),
+    }
+  }
+}
+
+impl std::error::Error for PackError {}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ManifestEntry {
+  Surface {
+    name: String,
+    file: String,
+  },
+  // `cells` is the size of the grid, sheets with JSON metadata have none
+  SpriteSheet {
+    name: String,
+    file: String,
+    cells: Option<V2U>,
+  },
+  Font {
+    name: String,
+    file: String,
+    glyphs: Option<(V2U, String)>,
+  },
+  Sfx {
+    name: String,
+    file: String,
+  },
+}
+
+pub struct AssetPack {
+  files: HashMap<String, Vec<u8>>,
+  // set for packs read from a directory, whose files can be reloaded
+  dir: Option<PathBuf>,
+}
+
+impl AssetPack {
+  // a tar archive, as made by `tar cf pack.tar -C assets .`
+  pub fn from_tar(data: &[u8]) -> Result<AssetPack, PackError> {
+    let mut files = HashMap::new();
+    let mut long_name: Option<String> = None;
+    let mut offset = 0;
+    while offset + 512 <= data.len() {
+      let header = &data[offset..offset + 512];
+      if header.iter().all(|b| *b == 0) {
+        break;
+      }
+      let size =
+        parse_octal(&header[124..136]).ok_or(PackError::InvalidArchive("invalid entry size"))?;
+      let start = offset + 512;
+      let contents = data
+        .get(start..start + size)
+        .ok_or(PackError::InvalidArchive("truncated entry"))?;
+      offset = start + size.div_ceil(512) * 512;
+
+      let name = match long_name.take() {
+        Some(name) => name,
+        None => header_name(header),
+      };
+      match header[156] {
+        b'0' | 0 => {
+          files.insert(normalize(&name), contents.to_vec());
+        }
+        // GNU long names come in an entry of their own, before the file
+        b'L' => {
+          long_name = Some(
+            String::from_utf8_lossy(contents)
+              .trim_end_matches('\0')
+              .to_string(),
+          )
+        }
+        // directories, links and extended headers
+        _ => {}
+      }
+    }
+    Ok(AssetPack { files, dir: None })
+  }
+
+  // the files in the directory and its subdirectories
+  pub fn from_dir<P: AsRef<Path>>(dir: P) -> Result<AssetPack, PackError> {
+    let dir = dir.as_ref();
+    let mut files = HashMap::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+      let entries = std::fs::read_dir(&current).map_err(|e| io_error(&current, e))?;
+      for entry in entries {
+        let path = entry.map_err(|e| io_error(&current, e))?.path();
+        if path.is_dir() {
+          dirs.push(path);
+          continue;
+        }
+        let data = std::fs::read(&path).map_err(|e| io_error(&path, e))?;
+        let name = path.strip_prefix(dir).unwrap().to_string_lossy();
+        files.insert(normalize(&name), data);
+      }
+    }
+    Ok(AssetPack {
+      files,
+      dir: Some(dir.to_path_buf()),
+    })
+  }
+
+  // a directory or a tar archive
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<AssetPack, PackError> {
+    let path = path.as_ref();
+    if path.is_dir() {
+      AssetPack::from_dir(path)
+    } else {
+      AssetPack::from_tar(&std::fs::read(path).map_err(|e| io_error(path, e))?)
+    }
+  }
+
+  // paths are relative to the pack and use '/'
+  pub fn file(&self, file: &str) -> Result<&[u8], PackError> {
+    self
+      .files
+      .get(&normalize(file))
+      .map(|data| data.as_slice())
+      .ok_or_else(|| PackError::MissingFile(file.to_string()))
+  }
+
+  pub fn text(&self, file: &str) -> Result<&str, PackError> {
+    std::str::from_utf8(self.file(file)?)
+      .map_err(|_| PackError::InvalidFile(file.to_string(), "not UTF-8".to_string()))
+  }
+
+  // where the file is on disk, None for archives
+  pub fn path(&self, file: &str) -> Option<PathBuf> {
+    self.dir.as_ref().map(|dir| dir.join(normalize(file)))
+  }
+
+  pub fn manifest(&self) -> Result<Vec<ManifestEntry>, PackError> {
+    parse_manifest(self.text(MANIFEST_FILE)?)
+  }
+}
+
+pub fn parse_manifest(text: &str) -> Result<Vec<ManifestEntry>, PackError> {
+  let mut entries = Vec::new();
+  for (ix, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let invalid = || PackError::InvalidManifestLine(ix + 1, line.to_string());
+    let (kind, rest) = split_word(line);
+    let (name, rest) = split_word(rest);
+    let (file, rest) = split_word(rest);
+    if file.is_empty() {
+      return Err(invalid());
+    }
+    let (name, file) = (name.to_string(), file.to_string());
+    let (size, chars) = split_word(rest);
+    let entry = match (kind, size) {
+      ("surface", "") => ManifestEntry::Surface { name, file },
+      ("sprite_sheet", _) if chars.is_empty() => ManifestEntry::SpriteSheet {
+        name,
+        file,
+        cells: match size {
+          "" => None,
+          size => Some(parse_size(size).ok_or_else(invalid)?),
+        },
+      },
+      ("font", "") => ManifestEntry::Font {
+        name,
+        file,
+        glyphs: None,
+      },
+      ("font", size) if !chars.is_empty() => ManifestEntry::Font {
+        name,
+        file,
+        glyphs: Some((parse_size(size).ok_or_else(invalid)?, chars.to_string())),
+      },
+      ("sfx", "") => ManifestEntry::Sfx { name, file },
+      _ => return Err(invalid()),
+    };
+    entries.push(entry);
+  }
+  Ok(entries)
+}
+
+// the first word and what follows the whitespace after it, which keeps any further whitespace
+fn split_word(text: &str) -> (&str, &str) {
+  let text = text.trim_start();
+  match text.find(char::is_whitespace) {
+    Some(end) => {
+      let rest = &text[end..];
+      let space = rest.chars().next().unwrap().len_utf8();
+      (&text[..end], &rest[space..])
+    }
+    None => (text, ""),
+  }
+}
+
+// e.g. "4x2", neither may be 0
+fn parse_size(text: &str) -> Option<V2U> {
+  let (w, h) = text.split_once('x')?;
+  let size = V2U::new(w.parse().ok()?, h.parse().ok()?);
+  if size.x == 0 || size.y == 0 {
+    return None;
+  }
+  Some(size)
+}
+
+fn parse_octal(field: &[u8]) -> Option<usize> {
+  let text = std::str::from_utf8(field).ok()?;
+  let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+  if text.is_empty() {
+    return Some(0);
+  }
+  usize::from_str_radix(text, 8).ok()
+}
+
+// the ustar prefix, if any, and the name
+fn header_name(header: &[u8]) -> String {
+  let field = |bytes: &[u8]| {
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).to_string()
+  };
+  let name = field(&header[0..100]);
+  if &header[257..262] == b"ustar" {
+    let prefix = field(&header[345..500]);
+    if !prefix.is_empty() {
+      return format!("{}/{}", prefix, name);
+    }
+  }
+  name
+}
+
+fn normalize(name: &str) -> String {
+  let name = name.replace('\\', "/");
+  name.trim_start_matches("./").to_string()
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> PackError {
+  PackError::Io(path.display().to_string(), e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn tar_entry(name: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = vec![0; 512];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+    header[156] = b'0';
+    header[257..262].copy_from_slice(b"ustar");
+    let mut entry = header;
+    entry.extend_from_slice(data);
+    entry.resize(entry.len() + (512 - data.len() % 512) % 512, 0);
+    entry
+  }
+
+  #[test]
+  fn reads_tar_archives() {
+    let mut tar = tar_entry("./manifest.txt", b"surface ball ball.txt\n");
+    tar.extend(tar_entry("./ball.txt", &[b'#'; 600]));
+    tar.extend(vec![0; 1024]);
+    let pack = AssetPack::from_tar(&tar).unwrap();
+    assert_eq!(pack.file("ball.txt").unwrap().len(), 600);
+    assert_eq!(
+      pack.file("coin.txt"),
+      Err(PackError::MissingFile("coin.txt".to_string()))
+    );
+    assert_eq!(
+      pack.manifest().unwrap(),
+      vec![ManifestEntry::Surface {
+        name: "ball".to_string(),
+        file: "ball.txt".to_string()
+      }]
+    );
+    assert_eq!(pack.path("ball.txt"), None);
+
+    tar.truncate(1600);
+    assert_eq!(
+      AssetPack::from_tar(&tar).err(),
+      Some(PackError::InvalidArchive("truncated entry"))
+    );
+  }
+
+  #[test]
+  fn parses_manifests() {
+    let text =
+      "# art\nsprite_sheet items items.png 4x2\nfont big big.png 6x8  ab\nsfx hit hit.rtttl";
+    assert_eq!(
+      parse_manifest(text).unwrap(),
+      vec![
+        ManifestEntry::SpriteSheet {
+          name: "items".to_string(),
+          file: "items.png".to_string(),
+          cells: Some(V2U::new(4, 2)),
+        },
+        ManifestEntry::Font {
+          name: "big".to_string(),
+          file: "big.png".to_string(),
+          glyphs: Some((V2U::new(6, 8), " ab".to_string())),
+        },
+        ManifestEntry::Sfx {
+          name: "hit".to_string(),
+          file: "hit.rtttl".to_string(),
+        },
+      ]
+    );
+    assert!(parse_manifest("font big big.png 0x8 abc").is_err());
+    assert_eq!(
+      parse_manifest("\nsurface ball").err(),
+      Some(PackError::InvalidManifestLine(
+        2,
+        "surface ball".to_string()
+      ))
+    );
+  }
+}
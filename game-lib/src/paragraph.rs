@@ -0,0 +1,261 @@
+// wrapped text boxes
+//
+// A paragraph is word-wrapped to fit its box, taller text shows `max_lines` lines at a time
+// starting at the scrolled to line, with arrows on the right when there is more above or
+// below. For dialogue, `reveal` shows only the first characters of the visible lines.
+use crate::assets::*;
+use crate::color::*;
+use crate::config::*;
+use crate::font::*;
+use crate::renderer::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+  Left,
+  Center,
+  Right,
+}
+
+pub struct Paragraph {
+  text: String,
+  font: Option<FontId>,
+  hor_pos: HorPos,
+  vert_pos: VertPos,
+  width: Option<u32>,
+  max_lines: Option<usize>,
+  align: TextAlign,
+  background_color: Color,
+  indicator_color: Color,
+  first_line: usize,
+  revealed_chars: Option<usize>,
+}
+
+const INDICATOR_WIDTH: u32 = 4;
+
+impl Paragraph {
+  // centered, as wide and tall as the screen allows, in the default font and cell phone colors
+  pub fn new(text: &str) -> Paragraph {
+    Paragraph {
+      text: text.to_string(),
+      font: None,
+      hor_pos: HorPos::Center,
+      vert_pos: VertPos::Center,
+      width: None,
+      max_lines: None,
+      align: TextAlign::Left,
+      background_color: BRIGHT_COLOR,
+      indicator_color: DARK_COLOR,
+      first_line: 0,
+      revealed_chars: None,
+    }
+  }
+
+  pub fn font(self, font_id: FontId) -> Paragraph {
+    Paragraph {
+      font: Some(font_id),
+      ..self
+    }
+  }
+
+  pub fn position(self, hor_pos: HorPos, vert_pos: VertPos) -> Paragraph {
+    Paragraph {
+      hor_pos,
+      vert_pos,
+      ..self
+    }
+  }
+
+  // the widest the box may get, including its padding
+  pub fn width(self, width: u32) -> Paragraph {
+    Paragraph {
+      width: Some(width),
+      ..self
+    }
+  }
+
+  pub fn max_lines(self, max_lines: usize) -> Paragraph {
+    Paragraph {
+      max_lines: Some(max_lines.max(1)),
+      ..self
+    }
+  }
+
+  pub fn align(self, align: TextAlign) -> Paragraph {
+    Paragraph { align, ..self }
+  }
+
+  pub fn colors(self, background_color: Color, indicator_color: Color) -> Paragraph {
+    Paragraph {
+      background_color,
+      indicator_color,
+      ..self
+    }
+  }
+
+  // scrolling past the end shows the last page, paging is scrolling by `max_lines`
+  pub fn scroll(self, first_line: usize) -> Paragraph {
+    Paragraph { first_line, ..self }
+  }
+
+  // counts the characters of the visible lines, without the spaces wrapped lines break at
+  pub fn reveal(self, chars: usize) -> Paragraph {
+    Paragraph {
+      revealed_chars: Some(chars),
+      ..self
+    }
+  }
+
+  pub fn draw<R: Renderer>(&self, renderer: &mut R) {
+    let screen_size = renderer.screen_size();
+    let (_, assets) = renderer.target_and_assets();
+    let font = assets.font_or_default(self.font);
+    let line_height = font.line_height() + 1;
+    let box_width = self.width.unwrap_or(screen_size.x).min(screen_size.x);
+    let max_lines = self
+      .max_lines
+      .unwrap_or(((screen_size.y.saturating_sub(1)) / line_height).max(1) as usize);
+
+    let mut lines = wrap_text(font, &self.text, box_width.saturating_sub(1));
+    let scrolls = lines.len() > max_lines;
+    if scrolls {
+      lines = wrap_text(
+        font,
+        &self.text,
+        box_width.saturating_sub(1 + INDICATOR_WIDTH),
+      );
+    }
+    let text_width = lines
+      .iter()
+      .map(|line| font.text_width(line))
+      .max()
+      .unwrap_or(0);
+    let indicator_width = if scrolls { INDICATOR_WIDTH } else { 0 };
+    let w = text_width + indicator_width + 1;
+    let h = max_lines.min(lines.len()) as u32 * line_height + 1;
+    let x = self.hor_pos.resolve(w, screen_size.x);
+    let y = self.vert_pos.resolve(h, screen_size.y);
+
+    let first_line = self.first_line.min(lines.len().saturating_sub(max_lines));
+    let last_line = (first_line + max_lines).min(lines.len());
+    let mut remaining = self.revealed_chars.unwrap_or(usize::MAX);
+    let (target, assets) = renderer.target_and_assets();
+    let font = assets.font_or_default(self.font);
+    target.fill_rect(x, y, w, h, self.background_color);
+    for (ix, line) in lines[first_line..last_line].iter().enumerate() {
+      let shown: String = line.chars().take(remaining).collect();
+      remaining -= shown.chars().count();
+      let slack = (text_width - font.text_width(line)) as i32;
+      let line_x = match self.align {
+        TextAlign::Left => 0,
+        TextAlign::Center => slack / 2,
+        TextAlign::Right => slack,
+      };
+      font.draw(
+        target,
+        x + 1 + line_x,
+        y + 1 + (ix as u32 * line_height) as i32,
+        &shown,
+      );
+    }
+
+    // 3x2 arrows in the right margin
+    let arrow_x = x + w as i32 - INDICATOR_WIDTH as i32;
+    if first_line > 0 {
+      target.set_pixel(arrow_x + 1, y + 1, self.indicator_color);
+      target.fill_rect(arrow_x, y + 2, 3, 1, self.indicator_color);
+    }
+    if last_line < lines.len() {
+      let arrow_y = y + h as i32 - 3;
+      target.fill_rect(arrow_x, arrow_y, 3, 1, self.indicator_color);
+      target.set_pixel(arrow_x + 1, arrow_y + 1, self.indicator_color);
+    }
+  }
+}
+
+// greedy word wrap to lines at most `max_width` wide as measured by `Font::text_width`, words
+// longer than a line are broken between characters and '\n' starts a new line
+pub fn wrap_text(font: &Font, text: &str, max_width: u32) -> Vec<String> {
+  let mut lines = Vec::new();
+  for paragraph in text.split('\n') {
+    let mut line = String::new();
+    for word in paragraph.split_whitespace() {
+      let candidate = if line.is_empty() {
+        word.to_string()
+      } else {
+        format!("{} {}", line, word)
+      };
+      if font.text_width(&candidate) <= max_width {
+        line = candidate;
+        continue;
+      }
+      if !line.is_empty() {
+        lines.push(std::mem::take(&mut line));
+      }
+      for ch in word.chars() {
+        line.push(ch);
+        if font.text_width(&line) > max_width && line.chars().count() > 1 {
+          line.pop();
+          lines.push(std::mem::take(&mut line));
+          line.push(ch);
+        }
+      }
+    }
+    lines.push(line);
+  }
+  lines
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::image::*;
+
+  #[test]
+  fn wraps_words() {
+    let font = Font::builtin(Color::WHITE);
+    // 5 pixels per character
+    let lines = wrap_text(&font, "the quick brown fox\n\nend", 50);
+    assert_eq!(lines, vec!["the quick", "brown fox", "", "end"]);
+    let lines = wrap_text(&font, "abcdefgh ij", 20);
+    assert_eq!(lines, vec!["abcd", "efgh", "ij"]);
+  }
+
+  #[test]
+  fn scrolls_and_reveals() {
+    let assets = Assets::new(DARK_COLOR);
+    let render = |paragraph: Paragraph| {
+      let mut image = Image::filled(84, 48, Color::WHITE);
+      paragraph.draw(&mut Frame {
+        target: &mut image,
+        assets: &assets,
+      });
+      image
+    };
+    let text = "one two three four";
+    let paragraph = || {
+      Paragraph::new(text)
+        .position(HorPos::Left, VertPos::Top)
+        .width(30)
+        .max_lines(2)
+    };
+
+    // "one" "two" "three" "four" in 4 lines at most 25 pixels wide, plus the arrows
+    let top = render(paragraph());
+    assert_eq!(top.get(27, 1), Some(BRIGHT_COLOR));
+    assert_eq!(top.get(26, 10), Some(DARK_COLOR));
+    assert_eq!(top.get(26, 11), Some(BRIGHT_COLOR));
+    assert_eq!(top.get(27, 11), Some(DARK_COLOR));
+    assert_eq!(top.get(30, 0), Some(Color::WHITE));
+
+    // scrolling past the end shows the last two lines, with an arrow up only
+    let bottom = render(paragraph().scroll(10));
+    assert_eq!(bottom.get(27, 1), Some(DARK_COLOR));
+    assert_eq!(bottom.get(26, 10), Some(BRIGHT_COLOR));
+
+    let hidden = render(paragraph().reveal(0));
+    let revealed = render(paragraph().reveal(3));
+    let shown = |image: &Image| (1..6).any(|y| image.get(1, y) == Some(DARK_COLOR));
+    assert!(!shown(&hidden));
+    assert!(shown(&revealed));
+  }
+}
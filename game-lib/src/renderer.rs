@@ -0,0 +1,446 @@
+use crate::animation::*;
+use crate::assets::*;
+use crate::color::*;
+use crate::error::*;
+use crate::image::*;
+use crate::paragraph::*;
+use crate::transform::*;
+use crate::types::*;
+
+// something pixels can be drawn into, coordinates outside of `size` are ignored
+pub trait DrawTarget {
+  fn size(&self) -> V2U;
+  fn get_pixel(&self, x: i32, y: i32) -> Option<Color>;
+  fn set_pixel(&mut self, x: i32, y: i32, color: Color);
+
+  fn fill_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
+    for dy in 0..h as i32 {
+      for dx in 0..w as i32 {
+        self.set_pixel(x + dx, y + dy, color);
+      }
+    }
+  }
+
+  fn blit(&mut self, src: &ImageRegion, x: i32, y: i32) {
+    blit_pixels(self, src, x, y, None);
+  }
+
+  // like `blit`, but the opaque parts of `src` are drawn in `color`, e.g. for colored text
+  fn blit_colored(&mut self, src: &ImageRegion, x: i32, y: i32, color: Color) {
+    blit_pixels(self, src, x, y, Some(color));
+  }
+
+  // replaces every pixel by `f` of it
+  fn map_pixels(&mut self, f: &mut dyn FnMut(Color) -> Color) {
+    let size = self.size();
+    for y in 0..size.y as i32 {
+      for x in 0..size.x as i32 {
+        if let Some(color) = self.get_pixel(x, y) {
+          self.set_pixel(x, y, f(color));
+        }
+      }
+    }
+  }
+}
+
+fn blit_pixels<T: DrawTarget + ?Sized>(
+  target: &mut T,
+  src: &ImageRegion,
+  x: i32,
+  y: i32,
+  tint: Option<Color>,
+) {
+  for dy in 0..src.size.y {
+    for dx in 0..src.size.x {
+      let (px, py) = (x + dx as i32, y + dy as i32);
+      let src_color = src
+        .image
+        .get((src.pos.x + dx) as i32, (src.pos.y + dy) as i32)
+        .map(|c| tint.map_or(c, |tint| c.tint(tint)));
+      if let (Some(src_color), Some(dst_color)) = (src_color, target.get_pixel(px, py)) {
+        target.set_pixel(px, py, src_color.blend_over(dst_color));
+      }
+    }
+  }
+}
+
+impl DrawTarget for Image {
+  fn size(&self) -> V2U {
+    Image::size(self)
+  }
+
+  fn get_pixel(&self, x: i32, y: i32) -> Option<Color> {
+    self.get(x, y)
+  }
+
+  fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
+    if let Some(pixel) = self.get_mut(x, y) {
+      *pixel = color;
+    }
+  }
+}
+
+// only 32 bit pixel formats are supported
+impl DrawTarget for sdl2::surface::Surface<'_> {
+  fn size(&self) -> V2U {
+    V2U::new(self.width(), self.height())
+  }
+
+  fn get_pixel(&self, x: i32, y: i32) -> Option<Color> {
+    let off = surface_offset(self, x, y)?;
+    let format = self.pixel_format();
+    let mut bytes = [0; 4];
+    self.with_lock(|surf: &[u8]| bytes.copy_from_slice(&surf[off..off + 4]));
+    Some(sdl2::pixels::Color::from_u32(&format, u32::from_ne_bytes(bytes)).into())
+  }
+
+  fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
+    if let Some(off) = surface_offset(self, x, y) {
+      let pixel = sdl2::pixels::Color::from(color).to_u32(&self.pixel_format());
+      self.with_lock_mut(|surf: &mut [u8]| {
+        surf[off..off + 4].copy_from_slice(&pixel.to_ne_bytes());
+      });
+    }
+  }
+
+  fn fill_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
+    let rect = sdl2::rect::Rect::new(x, y, w, h);
+    if let Err(e) = sdl2::surface::SurfaceRef::fill_rect(self, rect, color.into()) {
+      log_error(GameError::Sdl("fill a rect", e));
+    }
+  }
+
+  // same as the default, but locks the surface only once
+  fn blit(&mut self, src: &ImageRegion, x: i32, y: i32) {
+    blit_surface(self, src, x, y, None);
+  }
+
+  fn blit_colored(&mut self, src: &ImageRegion, x: i32, y: i32, color: Color) {
+    blit_surface(self, src, x, y, Some(color));
+  }
+
+  // same as the default, but locks the surface only once
+  fn map_pixels(&mut self, f: &mut dyn FnMut(Color) -> Color) {
+    let format = self.pixel_format();
+    let (width, height, pitch) = (self.width() as usize, self.height() as usize, self.pitch());
+    self.with_lock_mut(|surf: &mut [u8]| {
+      for y in 0..height {
+        for x in 0..width {
+          let off = y * pitch as usize + x * 4;
+          let mut bytes = [0; 4];
+          bytes.copy_from_slice(&surf[off..off + 4]);
+          let color = f(sdl2::pixels::Color::from_u32(&format, u32::from_ne_bytes(bytes)).into());
+          let pixel = sdl2::pixels::Color::from(color).to_u32(&format);
+          surf[off..off + 4].copy_from_slice(&pixel.to_ne_bytes());
+        }
+      }
+    });
+  }
+}
+
+fn blit_surface(
+  surface: &mut sdl2::surface::SurfaceRef,
+  src: &ImageRegion,
+  x: i32,
+  y: i32,
+  tint: Option<Color>,
+) {
+  let format = surface.pixel_format();
+  let offsets: Vec<(usize, Color)> = (0..src.size.y)
+    .flat_map(|dy| (0..src.size.x).map(move |dx| (dx, dy)))
+    .filter_map(|(dx, dy)| {
+      let off = surface_offset(surface, x + dx as i32, y + dy as i32)?;
+      let color = src
+        .image
+        .get((src.pos.x + dx) as i32, (src.pos.y + dy) as i32)?;
+      Some((off, tint.map_or(color, |tint| color.tint(tint))))
+    })
+    .collect();
+  surface.with_lock_mut(|surf: &mut [u8]| {
+    for (off, src_color) in offsets {
+      let mut bytes = [0; 4];
+      bytes.copy_from_slice(&surf[off..off + 4]);
+      let dst_color: Color =
+        sdl2::pixels::Color::from_u32(&format, u32::from_ne_bytes(bytes)).into();
+      let pixel = sdl2::pixels::Color::from(src_color.blend_over(dst_color)).to_u32(&format);
+      surf[off..off + 4].copy_from_slice(&pixel.to_ne_bytes());
+    }
+  });
+}
+
+fn surface_offset(surface: &sdl2::surface::SurfaceRef, x: i32, y: i32) -> Option<usize> {
+  if x < 0 || surface.width() as i32 <= x || y < 0 || surface.height() as i32 <= y {
+    return None;
+  }
+  Some(y as usize * surface.pitch() as usize + x as usize * 4)
+}
+
+// drawing primitives, implementors only need to hand out their target and assets
+pub trait Renderer {
+  fn target_and_assets(&mut self) -> (&mut dyn DrawTarget, &Assets);
+
+  fn screen_size(&mut self) -> V2U {
+    self.target_and_assets().0.size()
+  }
+
+  fn draw_pixel(&mut self, x: i32, y: i32, color: Color) {
+    self.target_and_assets().0.set_pixel(x, y, color);
+  }
+
+  fn draw_rect(&mut self, x: i32, y: i32, w: u32, h: u32, color: Color) {
+    self.target_and_assets().0.fill_rect(x, y, w, h, color);
+  }
+
+  // midpoint circle algorithm
+  fn draw_circle(&mut self, cx: i32, cy: i32, r: i32, m: i32, color: Color) {
+    let mut f = 1 - r;
+    let mut dx = 0;
+    let mut dy = -2 * r;
+    let mut x = 0;
+    let mut y = r;
+
+    self.draw_pixel(cx, cy + r, color);
+    self.draw_pixel(cx, cy - r, color);
+    self.draw_pixel(cx + r, cy, color);
+    self.draw_pixel(cx - r, cy, color);
+
+    while x < y {
+      if f >= 0 {
+        y -= 1;
+        dy += 2;
+        f += dy;
+      }
+      x += 1;
+      dx += 2;
+      f += dx + 1;
+      if x % m == 0 {
+        self.draw_pixel(cx + x, cy + y, color);
+        self.draw_pixel(cx - x, cy + y, color);
+        self.draw_pixel(cx + x, cy - y, color);
+        self.draw_pixel(cx - x, cy - y, color);
+        self.draw_pixel(cx + y, cy + x, color);
+        self.draw_pixel(cx - y, cy + x, color);
+        self.draw_pixel(cx + y, cy - x, color);
+        self.draw_pixel(cx - y, cy - x, color);
+      }
+    }
+  }
+
+  fn draw_text(&mut self, x: i32, y: i32, text: &str) {
+    let (target, assets) = self.target_and_assets();
+    assets.default_font().draw(target, x, y, text);
+  }
+
+  // `background_color` fills the text's box, with a pixel of padding
+  fn draw_text_colored(
+    &mut self,
+    x: i32,
+    y: i32,
+    text: &str,
+    color: Color,
+    background_color: Option<Color>,
+  ) {
+    let (target, assets) = self.target_and_assets();
+    let font = assets.default_font();
+    if let Some(background_color) = background_color {
+      target.fill_rect(
+        x - 1,
+        y - 1,
+        font.text_width(text) + 1,
+        font.line_height() + 2,
+        background_color,
+      );
+    }
+    font.draw_colored(target, x, y, text, color);
+  }
+
+  // see `parse_markup`
+  fn draw_markup(&mut self, x: i32, y: i32, text: &str, palette: &Palette) {
+    let (target, assets) = self.target_and_assets();
+    assets
+      .default_font()
+      .draw_markup(target, x, y, text, palette);
+  }
+
+  fn draw_text_with_font(&mut self, font_id: FontId, x: i32, y: i32, text: &str) {
+    let (target, assets) = self.target_and_assets();
+    assets.font(font_id).draw(target, x, y, text);
+  }
+
+  fn draw_text_box<T: AsRef<str>>(
+    &mut self,
+    hor_pos: HorPos,
+    vert_pos: VertPos,
+    lines: &[T],
+    background_color: Color,
+  ) where
+    Self: Sized,
+  {
+    draw_text_box_in(self, None, hor_pos, vert_pos, lines, background_color);
+  }
+
+  fn draw_text_box_with_font<T: AsRef<str>>(
+    &mut self,
+    font_id: FontId,
+    hor_pos: HorPos,
+    vert_pos: VertPos,
+    lines: &[T],
+    background_color: Color,
+  ) where
+    Self: Sized,
+  {
+    draw_text_box_in(
+      self,
+      Some(font_id),
+      hor_pos,
+      vert_pos,
+      lines,
+      background_color,
+    );
+  }
+
+  fn draw_paragraph(&mut self, paragraph: &Paragraph)
+  where
+    Self: Sized,
+  {
+    paragraph.draw(self);
+  }
+
+  fn draw_image(&mut self, x: i32, y: i32, image: &Image) {
+    self.target_and_assets().0.blit(&image.region(), x, y);
+  }
+
+  fn draw_surface(&mut self, x: i32, y: i32, surface_id: SurfaceId) {
+    let (target, assets) = self.target_and_assets();
+    target.blit(&assets.surface(surface_id).region(), x, y);
+  }
+
+  fn draw_surface_with(&mut self, x: i32, y: i32, surface_id: SurfaceId, options: &DrawOptions) {
+    self.draw_frame_image(x, y, &FrameImage::Surface(surface_id), options);
+  }
+
+  fn draw_sprite(&mut self, x: i32, y: i32, sprite_id: SpriteId) {
+    let (target, assets) = self.target_and_assets();
+    target.blit(&assets.sprite(sprite_id), x, y);
+  }
+
+  fn draw_sprite_with(&mut self, x: i32, y: i32, sprite_id: SpriteId, options: &DrawOptions) {
+    self.draw_frame_image(x, y, &FrameImage::Sprite(sprite_id), options);
+  }
+
+  fn draw_animation(&mut self, x: i32, y: i32, player: &AnimationPlayer) {
+    self.draw_animation_with(x, y, player, &DrawOptions::new());
+  }
+
+  fn draw_animation_with(
+    &mut self,
+    x: i32,
+    y: i32,
+    player: &AnimationPlayer,
+    options: &DrawOptions,
+  ) {
+    if let Some(frame_image) = player.frame() {
+      self.draw_frame_image(x, y, frame_image, options);
+    }
+  }
+
+  // transformed images are cached by the assets
+  fn draw_frame_image(&mut self, x: i32, y: i32, frame_image: &FrameImage, options: &DrawOptions) {
+    let (target, assets) = self.target_and_assets();
+    if options.is_identity() {
+      target.blit(&assets.frame_image(frame_image), x, y);
+    } else {
+      target.blit(&assets.transformed(frame_image, options).region(), x, y);
+    }
+  }
+}
+
+// the box is sized to the widest line in the font, with a pixel of padding around the text
+fn draw_text_box_in<R: Renderer, T: AsRef<str>>(
+  renderer: &mut R,
+  font_id: Option<FontId>,
+  hor_pos: HorPos,
+  vert_pos: VertPos,
+  lines: &[T],
+  background_color: Color,
+) {
+  let screen_size = renderer.screen_size();
+  let (_, assets) = renderer.target_and_assets();
+  let font = assets.font_or_default(font_id);
+  let line_height = font.line_height() + 1;
+  let text_box_w: u32 = lines
+    .iter()
+    .map(|x| font.text_width(x.as_ref()))
+    .max()
+    .unwrap_or(0)
+    + 1;
+  let text_box_h: u32 = lines.len() as u32 * line_height + 1;
+  let dialogue_x = hor_pos.resolve(text_box_w, screen_size.x);
+  let dialogue_y = vert_pos.resolve(text_box_h, screen_size.y);
+  renderer.draw_rect(
+    dialogue_x,
+    dialogue_y,
+    text_box_w,
+    text_box_h,
+    background_color,
+  );
+  let (target, assets) = renderer.target_and_assets();
+  let font = assets.font_or_default(font_id);
+  for (ix, line) in lines.iter().enumerate() {
+    font.draw(
+      target,
+      dialogue_x + 1,
+      dialogue_y + (ix as u32 * line_height) as i32 + 1,
+      line.as_ref(),
+    );
+  }
+}
+
+// renders into any draw target, e.g. an offscreen `Image`
+pub struct Frame<'f> {
+  pub target: &'f mut dyn DrawTarget,
+  pub assets: &'f Assets,
+}
+
+impl<'f> Renderer for Frame<'f> {
+  fn target_and_assets(&mut self) -> (&mut dyn DrawTarget, &Assets) {
+    (self.target, self.assets)
+  }
+}
+
+pub enum HorPos {
+  Left,
+  Right,
+  Center,
+  Abs { x: i32 },
+}
+
+pub enum VertPos {
+  Top,
+  Bottom,
+  Center,
+  Abs { y: i32 },
+}
+
+impl HorPos {
+  // the left edge of something `width` wide on a screen `screen_width` wide
+  pub fn resolve(&self, width: u32, screen_width: u32) -> i32 {
+    match *self {
+      HorPos::Left => 0,
+      HorPos::Center => (screen_width as i32 - width as i32) / 2,
+      HorPos::Right => screen_width as i32 - width as i32,
+      HorPos::Abs { x } => x,
+    }
+  }
+}
+
+impl VertPos {
+  pub fn resolve(&self, height: u32, screen_height: u32) -> i32 {
+    match *self {
+      VertPos::Top => 0,
+      VertPos::Center => (screen_height as i32 - height as i32) / 2,
+      VertPos::Bottom => screen_height as i32 - height as i32,
+      VertPos::Abs { y } => y,
+    }
+  }
+}
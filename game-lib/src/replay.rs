@@ -0,0 +1,553 @@
+// Input recording and playback
+//
+// A replay file is a header (magic, version, rng seed) followed by the key and button
+// events of the session, each tagged with the game tick it was delivered before. Ticks are
+// stored as deltas and numbers as LEB128 varints to keep the files small.
+use crate::error::*;
+use crate::get_arg;
+use crate::keypad::PhoneKey;
+use sdl2::controller::{Axis, Button};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"PGRP";
+// bumped whenever event kinds are added, 2 added the mouse, keypad and gamepad events
+const VERSION: u8 = 2;
+
+const KIND_KEY_DOWN: u8 = 0;
+const KIND_KEY_REPEAT: u8 = 1;
+const KIND_KEY_UP: u8 = 2;
+const KIND_END: u8 = 3;
+const KIND_MOUSE_BUTTON_DOWN: u8 = 4;
+const KIND_MOUSE_BUTTON_UP: u8 = 5;
+const KIND_PHONE_KEY_DOWN: u8 = 6;
+const KIND_PHONE_KEY_UP: u8 = 7;
+const KIND_GAMEPAD_BUTTON_DOWN: u8 = 8;
+const KIND_GAMEPAD_BUTTON_UP: u8 = 9;
+const KIND_GAMEPAD_AXIS: u8 = 10;
+const KIND_POINTER_MOTION: u8 = 11;
+
+const GAMEPAD_BUTTONS: [Button; 15] = [
+  Button::A,
+  Button::B,
+  Button::X,
+  Button::Y,
+  Button::Back,
+  Button::Guide,
+  Button::Start,
+  Button::LeftStick,
+  Button::RightStick,
+  Button::LeftShoulder,
+  Button::RightShoulder,
+  Button::DPadUp,
+  Button::DPadDown,
+  Button::DPadLeft,
+  Button::DPadRight,
+];
+const GAMEPAD_AXES: [Axis; 6] = [
+  Axis::LeftX,
+  Axis::LeftY,
+  Axis::RightX,
+  Axis::RightY,
+  Axis::TriggerLeft,
+  Axis::TriggerRight,
+];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InputEvent {
+  KeyDown { keycode: Keycode, repeat: bool },
+  KeyUp { keycode: Keycode },
+  MouseButtonDown { button: MouseButton },
+  MouseButtonUp { button: MouseButton },
+  PhoneKeyDown { key: PhoneKey },
+  PhoneKeyUp { key: PhoneKey },
+  GamepadButtonDown { button: Button },
+  GamepadButtonUp { button: Button },
+  GamepadAxis { axis: Axis, value: i16 },
+  // in screen pixels
+  PointerMotion { x: i32, y: i32 },
+}
+
+impl InputEvent {
+  pub fn from_event(event: &Event) -> Option<InputEvent> {
+    match *event {
+      Event::KeyDown {
+        keycode: Some(keycode),
+        repeat,
+        ..
+      } => Some(InputEvent::KeyDown { keycode, repeat }),
+      Event::KeyUp {
+        keycode: Some(keycode),
+        ..
+      } => Some(InputEvent::KeyUp { keycode }),
+      Event::MouseButtonDown { mouse_btn, .. } => {
+        Some(InputEvent::MouseButtonDown { button: mouse_btn })
+      }
+      Event::MouseButtonUp { mouse_btn, .. } => {
+        Some(InputEvent::MouseButtonUp { button: mouse_btn })
+      }
+      Event::ControllerButtonDown { button, .. } => Some(InputEvent::GamepadButtonDown { button }),
+      Event::ControllerButtonUp { button, .. } => Some(InputEvent::GamepadButtonUp { button }),
+      Event::ControllerAxisMotion { axis, value, .. } => {
+        Some(InputEvent::GamepadAxis { axis, value })
+      }
+      _ => None,
+    }
+  }
+
+  // phone keys and pointer motion in screen pixels have no sdl event
+  pub fn to_event(&self) -> Option<Event> {
+    let event = match *self {
+      InputEvent::KeyDown { keycode, repeat } => Event::KeyDown {
+        timestamp: 0,
+        window_id: 0,
+        keycode: Some(keycode),
+        scancode: None,
+        keymod: sdl2::keyboard::Mod::NOMOD,
+        repeat,
+      },
+      InputEvent::KeyUp { keycode } => Event::KeyUp {
+        timestamp: 0,
+        window_id: 0,
+        keycode: Some(keycode),
+        scancode: None,
+        keymod: sdl2::keyboard::Mod::NOMOD,
+        repeat: false,
+      },
+      InputEvent::MouseButtonDown { button } => Event::MouseButtonDown {
+        timestamp: 0,
+        window_id: 0,
+        which: 0,
+        mouse_btn: button,
+        clicks: 1,
+        x: 0,
+        y: 0,
+      },
+      InputEvent::MouseButtonUp { button } => Event::MouseButtonUp {
+        timestamp: 0,
+        window_id: 0,
+        which: 0,
+        mouse_btn: button,
+        clicks: 1,
+        x: 0,
+        y: 0,
+      },
+      InputEvent::GamepadButtonDown { button } => Event::ControllerButtonDown {
+        timestamp: 0,
+        which: 0,
+        button,
+      },
+      InputEvent::GamepadButtonUp { button } => Event::ControllerButtonUp {
+        timestamp: 0,
+        which: 0,
+        button,
+      },
+      InputEvent::GamepadAxis { axis, value } => Event::ControllerAxisMotion {
+        timestamp: 0,
+        which: 0,
+        axis,
+        value,
+      },
+      InputEvent::PhoneKeyDown { .. }
+      | InputEvent::PhoneKeyUp { .. }
+      | InputEvent::PointerMotion { .. } => return None,
+    };
+    Some(event)
+  }
+}
+
+pub struct Recorder {
+  path: PathBuf,
+  writer: std::io::BufWriter<std::fs::File>,
+  last_tick: u32,
+}
+
+impl Recorder {
+  pub fn create(path: &str, seed: u64) -> Result<Recorder, GameError> {
+    let path = Path::new(path);
+    let io_error = |e| GameError::io(path, e);
+    let file = std::fs::File::create(path).map_err(io_error)?;
+    let mut writer = std::io::BufWriter::new(file);
+    writer.write_all(MAGIC).map_err(io_error)?;
+    writer.write_all(&[VERSION]).map_err(io_error)?;
+    writer.write_all(&seed.to_le_bytes()).map_err(io_error)?;
+    Ok(Recorder {
+      path: path.to_path_buf(),
+      writer,
+      last_tick: 0,
+    })
+  }
+
+  // write errors are logged, the recording is incomplete then but the game goes on
+  pub fn record(&mut self, tick: u32, input_event: &InputEvent) {
+    if let Err(e) = self.write_event(tick, input_event) {
+      log_error(GameError::io(&self.path, e));
+    }
+  }
+
+  fn write_event(&mut self, tick: u32, input_event: &InputEvent) -> std::io::Result<()> {
+    let (kind, code) = match *input_event {
+      InputEvent::KeyDown {
+        keycode,
+        repeat: false,
+      } => (KIND_KEY_DOWN, keycode as i32 as u32),
+      InputEvent::KeyDown {
+        keycode,
+        repeat: true,
+      } => (KIND_KEY_REPEAT, keycode as i32 as u32),
+      InputEvent::KeyUp { keycode } => (KIND_KEY_UP, keycode as i32 as u32),
+      InputEvent::MouseButtonDown { button } => (KIND_MOUSE_BUTTON_DOWN, button as u8 as u32),
+      InputEvent::MouseButtonUp { button } => (KIND_MOUSE_BUTTON_UP, button as u8 as u32),
+      InputEvent::PhoneKeyDown { key } => (KIND_PHONE_KEY_DOWN, key.index() as u32),
+      InputEvent::PhoneKeyUp { key } => (KIND_PHONE_KEY_UP, key.index() as u32),
+      InputEvent::GamepadButtonDown { button } => (KIND_GAMEPAD_BUTTON_DOWN, button as u32),
+      InputEvent::GamepadButtonUp { button } => (KIND_GAMEPAD_BUTTON_UP, button as u32),
+      InputEvent::GamepadAxis { axis, .. } => (KIND_GAMEPAD_AXIS, axis as u32),
+      InputEvent::PointerMotion { x, .. } => (KIND_POINTER_MOTION, zigzag(x)),
+    };
+    self.write_tick(tick)?;
+    self.writer.write_all(&[kind])?;
+    write_varint(&mut self.writer, code)?;
+    match *input_event {
+      InputEvent::GamepadAxis { value, .. } => write_varint(&mut self.writer, value as u16 as u32),
+      InputEvent::PointerMotion { y, .. } => write_varint(&mut self.writer, zigzag(y)),
+      _ => Ok(()),
+    }
+  }
+
+  pub fn finish(&mut self, tick: u32) {
+    let result = self
+      .write_tick(tick)
+      .and_then(|_| self.writer.write_all(&[KIND_END]))
+      .and_then(|_| self.writer.flush());
+    if let Err(e) = result {
+      log_error(GameError::io(&self.path, e));
+    }
+  }
+
+  fn write_tick(&mut self, tick: u32) -> std::io::Result<()> {
+    write_varint(&mut self.writer, tick - self.last_tick)?;
+    self.last_tick = tick;
+    Ok(())
+  }
+}
+
+pub struct Player {
+  pub seed: u64,
+  events: VecDeque<(u32, InputEvent)>,
+  end_tick: u32,
+}
+
+impl Player {
+  pub fn load(path: &str) -> Result<Player, GameError> {
+    let path = Path::new(path);
+    let file = std::fs::File::open(path).map_err(|e| GameError::io(path, e))?;
+    let mut reader = std::io::BufReader::new(file);
+    let seed = read_header(&mut reader, path)?;
+
+    let mut events = VecDeque::new();
+    let mut tick: u32 = 0;
+    // recordings of crashed sessions have no end marker
+    while let Some(delta) = read_varint(&mut reader) {
+      tick = tick
+        .checked_add(delta)
+        .ok_or_else(|| GameError::invalid_file(path, "tick out of range"))?;
+      let invalid = || GameError::invalid_file(path, format!("invalid event at tick {}", tick));
+      let mut kind = [0];
+      reader.read_exact(&mut kind).map_err(|_| invalid())?;
+      if kind[0] == KIND_END {
+        break;
+      }
+      let code = read_varint(&mut reader).ok_or_else(invalid)?;
+      let keycode = || Keycode::from_i32(code as i32).ok_or_else(invalid);
+      let button = || MouseButton::from_ll(code as u8);
+      let phone_key = || PhoneKey::from_index(code as u8).ok_or_else(invalid);
+      let gamepad_button = || {
+        GAMEPAD_BUTTONS
+          .iter()
+          .find(|&&button| button as u32 == code)
+          .copied()
+          .ok_or_else(invalid)
+      };
+      let gamepad_axis = || {
+        GAMEPAD_AXES
+          .iter()
+          .find(|&&axis| axis as u32 == code)
+          .copied()
+          .ok_or_else(invalid)
+      };
+      let input_event = match kind[0] {
+        KIND_KEY_DOWN => InputEvent::KeyDown {
+          keycode: keycode()?,
+          repeat: false,
+        },
+        KIND_KEY_REPEAT => InputEvent::KeyDown {
+          keycode: keycode()?,
+          repeat: true,
+        },
+        KIND_KEY_UP => InputEvent::KeyUp {
+          keycode: keycode()?,
+        },
+        KIND_MOUSE_BUTTON_DOWN => InputEvent::MouseButtonDown { button: button() },
+        KIND_MOUSE_BUTTON_UP => InputEvent::MouseButtonUp { button: button() },
+        KIND_PHONE_KEY_DOWN => InputEvent::PhoneKeyDown { key: phone_key()? },
+        KIND_PHONE_KEY_UP => InputEvent::PhoneKeyUp { key: phone_key()? },
+        KIND_GAMEPAD_BUTTON_DOWN => InputEvent::GamepadButtonDown {
+          button: gamepad_button()?,
+        },
+        KIND_GAMEPAD_BUTTON_UP => InputEvent::GamepadButtonUp {
+          button: gamepad_button()?,
+        },
+        KIND_GAMEPAD_AXIS => InputEvent::GamepadAxis {
+          axis: gamepad_axis()?,
+          value: read_varint(&mut reader).ok_or_else(invalid)? as u16 as i16,
+        },
+        KIND_POINTER_MOTION => InputEvent::PointerMotion {
+          x: unzigzag(code),
+          y: unzigzag(read_varint(&mut reader).ok_or_else(invalid)?),
+        },
+        _ => return Err(invalid()),
+      };
+      events.push_back((tick, input_event));
+    }
+
+    Ok(Player {
+      seed,
+      events,
+      end_tick: tick,
+    })
+  }
+
+  // the events that were delivered right before the update of `tick`
+  pub fn events_for_tick(&mut self, tick: u32) -> Vec<InputEvent> {
+    let mut result = Vec::new();
+    while let Some(&(event_tick, input_event)) = self.events.front() {
+      if event_tick > tick {
+        break;
+      }
+      result.push(input_event);
+      self.events.pop_front();
+    }
+    result
+  }
+
+  pub fn is_finished(&self, tick: u32) -> bool {
+    tick > self.end_tick
+  }
+}
+
+pub enum InputSource {
+  Live,
+  Recording(Recorder),
+  Replaying(Player),
+}
+
+impl InputSource {
+  // selected with `--record=<file>` or `--replay=<file>`
+  pub fn from_args() -> Result<InputSource, GameError> {
+    Ok(if let Some(path) = get_arg("replay") {
+      InputSource::Replaying(Player::load(&path)?)
+    } else if let Some(path) = get_arg("record") {
+      InputSource::Recording(Recorder::create(&path, session_seed())?)
+    } else {
+      InputSource::Live
+    })
+  }
+
+  // whether live input should be ignored at `tick`
+  pub fn is_replaying(&self, tick: u32) -> bool {
+    match self {
+      InputSource::Replaying(player) => !player.is_finished(tick),
+      _ => false,
+    }
+  }
+
+  pub fn record(&mut self, tick: u32, input_event: &InputEvent) {
+    if let InputSource::Recording(recorder) = self {
+      recorder.record(tick, input_event);
+    }
+  }
+
+  pub fn replayed_events(&mut self, tick: u32) -> Vec<InputEvent> {
+    match self {
+      InputSource::Replaying(player) => player.events_for_tick(tick),
+      _ => Vec::new(),
+    }
+  }
+
+  pub fn finish(&mut self, tick: u32) {
+    if let InputSource::Recording(recorder) = self {
+      recorder.finish(tick);
+    }
+  }
+}
+
+// The seed of the engine rng. It is read from the replay header when replaying, otherwise
+// taken from `--seed=<n>` or picked randomly, and stays the same for the whole process. An
+// unreadable replay is reported by `InputSource::from_args`.
+pub fn session_seed() -> u64 {
+  static INIT: std::sync::Once = std::sync::Once::new();
+  static SEED: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+  INIT.call_once(|| {
+    let replay_seed = get_arg("replay").and_then(|path| {
+      let path = Path::new(&path);
+      let file = std::fs::File::open(path).ok()?;
+      read_header(&mut std::io::BufReader::new(file), path).ok()
+    });
+    let seed = replay_seed.unwrap_or_else(|| {
+      get_arg("seed")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_else(rand::random)
+    });
+    SEED.store(seed, std::sync::atomic::Ordering::SeqCst);
+  });
+  SEED.load(std::sync::atomic::Ordering::SeqCst)
+}
+
+fn read_header<R: Read>(reader: &mut R, path: &Path) -> Result<u64, GameError> {
+  let mut magic = [0; 4];
+  let mut version = [0];
+  let mut seed = [0; 8];
+  let not_a_replay =
+    || GameError::invalid_file(path, format!("not a version {} replay file", VERSION));
+  reader
+    .read_exact(&mut magic)
+    .and_then(|_| reader.read_exact(&mut version))
+    .map_err(|_| not_a_replay())?;
+  if &magic != MAGIC || version[0] != VERSION {
+    return Err(not_a_replay());
+  }
+  reader.read_exact(&mut seed).map_err(|_| not_a_replay())?;
+  Ok(u64::from_le_bytes(seed))
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u32) -> std::io::Result<()> {
+  loop {
+    let byte = (value & 0x7f) as u8;
+    value >>= 7;
+    if value == 0 {
+      return writer.write_all(&[byte]);
+    }
+    writer.write_all(&[byte | 0x80])?;
+  }
+}
+
+// None at the end of the input and for varints longer than the 5 bytes a u32 takes
+fn read_varint<R: Read>(reader: &mut R) -> Option<u32> {
+  let mut value = 0;
+  for shift in (0..=28).step_by(7) {
+    let mut byte = [0];
+    reader.read_exact(&mut byte).ok()?;
+    // the last byte only has room for the top 4 bits
+    if shift == 28 && byte[0] & 0x70 != 0 {
+      return None;
+    }
+    value |= ((byte[0] & 0x7f) as u32) << shift;
+    if byte[0] & 0x80 == 0 {
+      return Some(value);
+    }
+  }
+  None
+}
+
+// maps small negative numbers to small varints
+fn zigzag(value: i32) -> u32 {
+  ((value << 1) ^ (value >> 31)) as u32
+}
+
+fn unzigzag(value: u32) -> i32 {
+  (value >> 1) as i32 ^ -((value & 1) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn temp_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+    path.to_string_lossy().to_string()
+  }
+
+  #[test]
+  fn replays_recorded_events() {
+    let path = temp_path("round-trip.replay");
+    let events = [
+      (
+        0,
+        InputEvent::KeyDown {
+          keycode: Keycode::Up,
+          repeat: false,
+        },
+      ),
+      (
+        3,
+        InputEvent::PhoneKeyDown {
+          key: PhoneKey::Num5,
+        },
+      ),
+      (
+        3,
+        InputEvent::GamepadAxis {
+          axis: Axis::LeftY,
+          value: -12000,
+        },
+      ),
+      (200, InputEvent::PointerMotion { x: -3, y: 40 }),
+    ];
+    let mut recorder = Recorder::create(&path, 42).unwrap();
+    for (tick, event) in &events {
+      recorder.record(*tick, event);
+    }
+    recorder.finish(250);
+    drop(recorder);
+
+    let mut player = Player::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(player.seed, 42);
+    let mut replayed = Vec::new();
+    for tick in 0..=250 {
+      replayed.extend(player.events_for_tick(tick).into_iter().map(|e| (tick, e)));
+    }
+    assert_eq!(replayed, events.to_vec());
+    assert!(!player.is_finished(250));
+    assert!(player.is_finished(251));
+  }
+
+  #[test]
+  fn rejects_broken_files() {
+    assert_eq!(
+      read_varint(&mut &[0xff, 0xff, 0xff, 0xff, 0x0f][..]),
+      Some(u32::MAX)
+    );
+    assert_eq!(read_varint(&mut &[0x80, 0x80][..]), None);
+    assert_eq!(read_varint(&mut &[0xff, 0xff, 0xff, 0xff, 0x1f][..]), None);
+    assert_eq!(
+      read_varint(&mut &[0x80, 0x80, 0x80, 0x80, 0x80, 0x01][..]),
+      None
+    );
+
+    // an event cut off after its kind, then ticks beyond u32::MAX
+    let path = temp_path("broken.replay");
+    let mut header = MAGIC.to_vec();
+    header.push(VERSION);
+    header.extend_from_slice(&7u64.to_le_bytes());
+    let mut truncated = header.clone();
+    truncated.extend_from_slice(&[0, KIND_KEY_DOWN]);
+    let mut old_version = header.clone();
+    old_version[MAGIC.len()] = 1;
+    let mut overflowing = header;
+    for _ in 0..2 {
+      overflowing.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f, KIND_PHONE_KEY_DOWN, 0]);
+    }
+    for data in [truncated, old_version, overflowing] {
+      std::fs::write(&path, data).unwrap();
+      let result = Player::load(&path);
+      assert!(matches!(result, Err(GameError::InvalidFile(..))));
+    }
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,102 @@
+// xoshiro256** (https://prng.di.unimi.it/), seeded with splitmix64
+//
+// Owned by the engine so that runs are reproducible from their seed. The whole state is
+// plain data, so it can be copied into save states and restored exactly. Implements
+// `rand::RngCore`, so the usual `rand::Rng` methods like `gen_range` work on it.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct GameRng {
+  s: [u64; 4],
+}
+
+impl GameRng {
+  pub fn new(seed: u64) -> GameRng {
+    let mut x = seed;
+    let mut splitmix64 = || {
+      x = x.wrapping_add(0x9e3779b97f4a7c15);
+      let mut z = x;
+      z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+      z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+      z ^ (z >> 31)
+    };
+    GameRng {
+      s: [splitmix64(), splitmix64(), splitmix64(), splitmix64()],
+    }
+  }
+
+  pub fn to_bytes(&self) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    for (chunk, s) in bytes.chunks_mut(8).zip(&self.s) {
+      chunk.copy_from_slice(&s.to_le_bytes());
+    }
+    bytes
+  }
+
+  pub fn from_bytes(bytes: [u8; 32]) -> GameRng {
+    let mut s = [0; 4];
+    for (s, chunk) in s.iter_mut().zip(bytes.chunks(8)) {
+      let mut word = [0; 8];
+      word.copy_from_slice(chunk);
+      *s = u64::from_le_bytes(word);
+    }
+    GameRng { s }
+  }
+}
+
+impl rand::RngCore for GameRng {
+  fn next_u64(&mut self) -> u64 {
+    let result = self.s[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+    let t = self.s[1] << 17;
+    self.s[2] ^= self.s[0];
+    self.s[3] ^= self.s[1];
+    self.s[1] ^= self.s[2];
+    self.s[0] ^= self.s[3];
+    self.s[2] ^= t;
+    self.s[3] = self.s[3].rotate_left(45);
+    result
+  }
+
+  fn next_u32(&mut self) -> u32 {
+    (self.next_u64() >> 32) as u32
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+      let bytes = self.next_u64().to_le_bytes();
+      chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rand::RngCore;
+
+  fn take(rng: &mut GameRng, n: usize) -> Vec<u64> {
+    (0..n).map(|_| rng.next_u64()).collect()
+  }
+
+  #[test]
+  fn same_seed_same_sequence() {
+    let (mut a, mut b) = (GameRng::new(42), GameRng::new(42));
+    assert_eq!(take(&mut a, 8), take(&mut b, 8));
+    assert_ne!(
+      take(&mut GameRng::new(43), 8),
+      take(&mut GameRng::new(42), 8)
+    );
+  }
+
+  #[test]
+  fn restores_from_bytes_mid_sequence() {
+    let mut rng = GameRng::new(7);
+    take(&mut rng, 5);
+    let mut restored = GameRng::from_bytes(rng.to_bytes());
+    assert_eq!(restored, rng);
+    assert_eq!(take(&mut restored, 8), take(&mut rng, 8));
+  }
+}
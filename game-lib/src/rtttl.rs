@@ -0,0 +1,187 @@
+// RTTTL (Ring Tone Text Transfer Language) parsing
+//
+// A ringtone is `<name>:<defaults>:<notes>`, e.g. `tune:d=4,o=5,b=120:8c,8e,g,2c6.`. The
+// defaults set the duration (d), octave (o) and tempo in quarter notes per minute (b, 25 to
+// 900). Each note is `[duration]<c|d|e|f|g|a|b|h|p>[#][octave][.]`, where p is a rest and a
+// dot makes the note half again as long.
+use crate::beeper::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ringtone {
+  pub name: String,
+  pub notes: Vec<Note>,
+}
+
+impl Ringtone {
+  pub fn to_sound(&self) -> Sound {
+    Sound::sequence(self.notes.clone())
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum RtttlError {
+  // not three `:` separated sections
+  MissingSection,
+  InvalidDefault(String),
+  // the index of the note and the note itself
+  InvalidNote(usize, String),
+}
+
+impl std::fmt::Display for RtttlError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      RtttlError::MissingSection => write!(f, "expected <name>:<defaults>:<notes>"),
+      RtttlError::InvalidDefault(default) => write!(f, "invalid default '{}'", default),
+      RtttlError::InvalidNote(index, note) => write!(f, "invalid note {} '{}'", index + 1, note),
+    }
+  }
+}
+
+impl std::error::Error for RtttlError {}
+
+const DURATIONS: [u32; 6] = [1, 2, 4, 8, 16, 32];
+
+pub fn parse_rtttl(text: &str) -> Result<Ringtone, RtttlError> {
+  let mut sections = text.splitn(3, ':');
+  let (name, defaults, notes) = match (sections.next(), sections.next(), sections.next()) {
+    (Some(name), Some(defaults), Some(notes)) => (name, defaults, notes),
+    _ => return Err(RtttlError::MissingSection),
+  };
+
+  let (mut duration, mut octave, mut bpm) = (4, 6, 63);
+  for default in defaults.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+    let invalid = || RtttlError::InvalidDefault(default.to_string());
+    let (key, value) = default.split_once('=').ok_or_else(invalid)?;
+    let value: u32 = value.trim().parse().map_err(|_| invalid())?;
+    match key.trim() {
+      "d" if DURATIONS.contains(&value) => duration = value,
+      "o" if (3..=8).contains(&value) => octave = value,
+      // the spec's tempo range, which also keeps note lengths from overflowing
+      "b" if (25..=900).contains(&value) => bpm = value,
+      _ => return Err(invalid()),
+    }
+  }
+
+  let notes = notes
+    .split(',')
+    .map(str::trim)
+    .enumerate()
+    .map(|(index, note)| {
+      parse_note(note, duration, octave, bpm)
+        .ok_or_else(|| RtttlError::InvalidNote(index, note.to_string()))
+    })
+    .collect::<Result<Vec<Note>, RtttlError>>()?;
+
+  Ok(Ringtone {
+    name: name.trim().to_string(),
+    notes,
+  })
+}
+
+fn parse_note(note: &str, default_duration: u32, default_octave: u32, bpm: u32) -> Option<Note> {
+  let note = note.to_ascii_lowercase();
+  let digits_end = note
+    .find(|c: char| !c.is_ascii_digit())
+    .unwrap_or(note.len());
+  let duration = match &note[..digits_end] {
+    "" => default_duration,
+    digits => digits.parse().ok().filter(|d| DURATIONS.contains(d))?,
+  };
+  let mut rest = note[digits_end..].chars().peekable();
+
+  // semitones above c
+  let mut semitone: Option<i32> = match rest.next()? {
+    'c' => Some(0),
+    'd' => Some(2),
+    'e' => Some(4),
+    'f' => Some(5),
+    'g' => Some(7),
+    'a' => Some(9),
+    'b' | 'h' => Some(11),
+    'p' => None,
+    _ => return None,
+  };
+  if rest.peek() == Some(&'#') {
+    rest.next();
+    semitone = Some(semitone? + 1);
+  }
+  // the dot is allowed before or after the octave
+  let mut dotted = false;
+  if rest.peek() == Some(&'.') {
+    rest.next();
+    dotted = true;
+  }
+  let octave = match rest.peek() {
+    Some(c) if c.is_ascii_digit() => {
+      let octave = c.to_digit(10)?;
+      rest.next();
+      octave
+    }
+    _ => default_octave,
+  };
+  if rest.peek() == Some(&'.') {
+    rest.next();
+    dotted = true;
+  }
+  if rest.next().is_some() {
+    return None;
+  }
+
+  // a whole note is four beats
+  let mut duration_ms = 240_000 / (bpm * duration);
+  if dotted {
+    duration_ms += duration_ms / 2;
+  }
+  let frequency = match semitone {
+    // a4 is 440 Hz
+    Some(semitone) => {
+      let from_a4 = semitone - 9 + (octave as i32 - 4) * 12;
+      440.0 * 2f32.powf(from_a4 as f32 / 12.0)
+    }
+    None => 0.0,
+  };
+  Some(Note::new(frequency, duration_ms))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_defaults_and_notes() {
+    let ringtone = parse_rtttl("tune:d=4,o=5,b=120:8c,a,p,2c#6.,16a4").unwrap();
+    assert_eq!(ringtone.name, "tune");
+    let durations: Vec<u32> = ringtone.notes.iter().map(|n| n.duration_ms).collect();
+    assert_eq!(durations, vec![250, 500, 500, 1500, 125]);
+    let frequencies: Vec<f32> = ringtone.notes.iter().map(|n| n.frequency).collect();
+    assert!((frequencies[0] - 523.25).abs() < 0.1);
+    assert_eq!(frequencies[1], 880.0);
+    assert_eq!(frequencies[2], 0.0);
+    assert!((frequencies[3] - 1108.73).abs() < 0.1);
+    assert_eq!(frequencies[4], 440.0);
+  }
+
+  #[test]
+  fn dot_before_octave() {
+    let a = parse_rtttl("x:d=4,o=5,b=100:c.6").unwrap();
+    let b = parse_rtttl("x:d=4,o=5,b=100:c6.").unwrap();
+    assert_eq!(a.notes, b.notes);
+  }
+
+  #[test]
+  fn reports_errors() {
+    assert_eq!(parse_rtttl("no sections"), Err(RtttlError::MissingSection));
+    assert_eq!(
+      parse_rtttl("x:d=3:c"),
+      Err(RtttlError::InvalidDefault("d=3".to_string()))
+    );
+    assert_eq!(
+      parse_rtttl("x:d=32,b=999999999:c"),
+      Err(RtttlError::InvalidDefault("b=999999999".to_string()))
+    );
+    assert_eq!(
+      parse_rtttl("x:d=4:c,8x,e"),
+      Err(RtttlError::InvalidNote(1, "8x".to_string()))
+    );
+  }
+}
@@ -1,111 +1,209 @@
 use crate::config::*;
+use crate::error::*;
+use crate::game_state::*;
 use crate::gcontext::*;
 use crate::key_status::*;
+use crate::replay::*;
+use crate::rng::*;
+use crate::scene::*;
 use crate::types::*;
 use sdl2::event::Event;
-use std::convert::TryInto;
 
-pub fn run<S: 'static, I, U: 'static, R: 'static, H: 'static>(
+pub fn run<S, I, U, R, H>(
   config: Config,
   state: S,
   init: I,
   update: U,
   render: R,
   handle_event: H,
-) where
-  I: Fn(&mut GContext),
-  U: Fn(&mut S, &KeyStatus, u32),
-  R: Fn(&mut GContext, &S),
-  H: Fn(&mut S, &sdl2::event::Event),
+) -> Result<(), GameError>
+where
+  S: 'static,
+  I: Fn(&mut GContext) + 'static,
+  U: Fn(&mut S, &KeyStatus, u32, &mut GameRng) + 'static,
+  R: Fn(&mut GContext, &S) + 'static,
+  H: Fn(&mut S, &sdl2::event::Event) + 'static,
 {
-  let game = Game::new(config, state, init, update, render, handle_event);
-  emscripten_main_loop::run(game);
+  run_game(
+    config,
+    FnGame::new(state, init, update, render, handle_event),
+  )
+}
+
+// Fails when the game can't be set up, e.g. without a display, once it runs errors are logged
+// instead. On the web this never returns.
+pub fn run_game<G: GameState + 'static>(config: Config, state: G) -> Result<(), GameError> {
+  run_game_with(config, |_| Ok(state))
 }
 
-pub struct Game<'a, S, U, R, H>
+// for a first state that holds the handles of assets it adds to the context
+pub fn run_game_with<G, F>(config: Config, make_state: F) -> Result<(), GameError>
 where
-  U: Fn(&mut S, &KeyStatus, u32),
-  R: Fn(&mut GContext, &S),
-  H: Fn(&mut S, &sdl2::event::Event),
+  G: GameState + 'static,
+  F: FnOnce(&mut GContext) -> Result<G, GameError>,
 {
+  let game = Game::new_with(config, make_state)?;
+  emscripten_main_loop::run(game);
+  Ok(())
+}
+
+pub struct Game<'a> {
   gcontext: GContext<'a>,
-  state: S,
-  update: U,
-  render: R,
-  handle_event: H,
+  input_source: InputSource,
+  scenes: SceneStack,
 }
 
-const TICK_INTERVAL: u32 = 50;
+// ms per game tick
+pub const TICK_INTERVAL: u32 = 50;
+// how often debug builds check surface files for changes
+#[cfg(all(debug_assertions, not(target_os = "emscripten")))]
+const RELOAD_CHECK_TICKS: u32 = 10;
 
-impl<'a, S, U, R, H> Game<'a, S, U, R, H>
-where
-  U: Fn(&mut S, &KeyStatus, u32),
-  R: Fn(&mut GContext, &S),
-  H: Fn(&mut S, &sdl2::event::Event),
-{
-  pub fn new<I>(
-    config: Config,
-    state: S,
-    init: I,
-    update: U,
-    render: R,
-    handle_event: H,
-  ) -> Game<'a, S, U, R, H>
+impl<'a> Game<'a> {
+  pub fn new<G: GameState + 'static>(config: Config, state: G) -> Result<Game<'a>, GameError> {
+    Game::new_with(config, |_| Ok(state))
+  }
+
+  pub fn new_with<G, F>(config: Config, make_state: F) -> Result<Game<'a>, GameError>
   where
-    I: Fn(&mut GContext),
+    G: GameState + 'static,
+    F: FnOnce(&mut GContext) -> Result<G, GameError>,
   {
-    let mut gcontext = GContext::new(config);
-    init(&mut gcontext);
-    Game {
+    let mut gcontext = GContext::try_new(config)?;
+    let state = make_state(&mut gcontext)?;
+    let mut scenes = SceneStack::new();
+    scenes.change(
+      SceneChange::Push(Box::new(state)),
+      Transition::None,
+      &mut gcontext,
+    );
+    Ok(Game {
       gcontext,
-      state,
-      update,
-      render,
-      handle_event,
-    }
+      input_source: InputSource::from_args()?,
+      scenes,
+    })
   }
 
   pub fn game_loop(&mut self) {
-    let ms_since_start = self.gcontext.timer_subsystem.ticks();
+    let ms_since_start = self.gcontext.ms_since_start(TICK_INTERVAL);
     let delta_ticks = ms_since_start - self.gcontext.ms_since_start_last_frame;
     self.gcontext.ms_since_start_last_frame = ms_since_start;
 
-    for event in self.gcontext.event_pump.poll_iter() {
-      handle_system_events(
-        &self.gcontext.canvas,
-        &mut self.gcontext.want_to_quit,
-        &mut self.gcontext.window_size,
-        &mut self.gcontext.key_status,
-        &event,
-      );
-      (self.handle_event)(&mut self.state, &event);
+    let tick = self.gcontext.game_tick_counter;
+    let events: Vec<Event> = match &mut self.gcontext.display {
+      Some(display) => display.event_pump.poll_iter().collect(),
+      None => Vec::new(),
+    };
+    for event in events {
+      handle_system_events(&mut self.gcontext, &event);
+      match self.gcontext.input_events(&event) {
+        // live input is ignored while a replay is running
+        Some(_) if self.input_source.is_replaying(tick) => {}
+        Some(input_events) => {
+          for input_event in input_events {
+            self.input_source.record(tick, &input_event);
+            self.apply_input(&input_event);
+          }
+        }
+        None => self.dispatch(|state, ctx| state.on_event(ctx, &event)),
+      }
     }
 
     self.gcontext.ms_until_game_tick += delta_ticks;
     while self.gcontext.ms_until_game_tick > TICK_INTERVAL {
       self.gcontext.ms_until_game_tick -= TICK_INTERVAL;
-      (self.update)(
-        &mut self.state,
-        &self.gcontext.key_status,
-        self.gcontext.game_tick_counter,
-      );
+      for input_event in self
+        .input_source
+        .replayed_events(self.gcontext.game_tick_counter)
+      {
+        self.apply_input(&input_event);
+      }
+      self.scenes.tick(&mut self.gcontext);
+      // also after a pop that waited for its transition
+      if self.scenes.is_empty() {
+        self.gcontext.want_to_quit = true;
+      }
+      self.dispatch(|state, ctx| state.update(ctx));
+      self.gcontext.key_status.end_tick();
       self.gcontext.game_tick_counter += 1;
+      #[cfg(all(debug_assertions, not(target_os = "emscripten")))]
+      if self
+        .gcontext
+        .game_tick_counter
+        .is_multiple_of(RELOAD_CHECK_TICKS)
+      {
+        self.gcontext.assets.reload_changed_surfaces();
+      }
     }
 
     self.gcontext.reset_screen();
-    (self.render)(&mut self.gcontext, &mut self.state);
+    self.scenes.render(&mut self.gcontext);
     self.gcontext.present();
   }
+
+  // updates the key status, including the phone keys bound to keyboard keys, and hands the
+  // event to the game
+  fn apply_input(&mut self, input_event: &InputEvent) {
+    self.gcontext.key_status.apply(input_event);
+    if let Some(phone_key_event) = self.gcontext.keypad.translate(input_event) {
+      self.gcontext.key_status.apply(&phone_key_event);
+    }
+    if let Some(event) = input_event.to_event() {
+      self.dispatch(|state, ctx| state.on_event(ctx, &event));
+    }
+  }
+
+  // calls into the active scene, then carries out what it requested
+  fn dispatch<F>(&mut self, f: F)
+  where
+    F: FnOnce(&mut dyn GameState, &mut UpdateContext),
+  {
+    let scene = match self.scenes.active_mut() {
+      Some(scene) => scene,
+      None => return,
+    };
+    let mut ctx = UpdateContext::new(
+      &self.gcontext.key_status,
+      &self.gcontext.input_map,
+      self.gcontext.game_tick_counter,
+      &mut self.gcontext.rng,
+    );
+    f(scene, &mut ctx);
+    for request in ctx.into_requests() {
+      match request {
+        Request::Quit => self.gcontext.want_to_quit = true,
+        Request::SetWindowTitle(title) => self.gcontext.set_window_title(&title),
+        Request::ChangeState(state) => self.scenes.change(
+          SceneChange::ReplaceAll(state),
+          Transition::None,
+          &mut self.gcontext,
+        ),
+        Request::PlaySfx(sfx_name) => self.gcontext.play_sfx(&sfx_name),
+        Request::ChangeScene(change, transition) => {
+          self.scenes.change(change, transition, &mut self.gcontext)
+        }
+      }
+    }
+  }
+
+  pub fn gcontext(&self) -> &GContext<'a> {
+    &self.gcontext
+  }
+
+  pub fn gcontext_mut(&mut self) -> &mut GContext<'a> {
+    &mut self.gcontext
+  }
+
+  pub fn scenes(&self) -> &SceneStack {
+    &self.scenes
+  }
 }
 
-impl<'a, S, U, R, H> emscripten_main_loop::MainLoop for Game<'a, S, U, R, H>
-where
-  U: Fn(&mut S, &KeyStatus, u32),
-  R: Fn(&mut GContext, &S),
-  H: Fn(&mut S, &sdl2::event::Event),
-{
+impl<'a> emscripten_main_loop::MainLoop for Game<'a> {
   fn main_loop(&mut self) -> emscripten_main_loop::MainLoopEvent {
     if self.gcontext.want_to_quit {
+      self.scenes.on_quit();
+      self.input_source.finish(self.gcontext.game_tick_counter);
       emscripten_main_loop::MainLoopEvent::Terminate
     } else {
       self.game_loop();
@@ -114,35 +212,26 @@ where
   }
 }
 
-fn handle_system_events(
-  canvas: &sdl2::render::Canvas<sdl2::video::Window>,
-  want_to_quit: &mut bool,
-  window_size: &mut V2U,
-  key_status: &mut KeyStatus,
-  event: &sdl2::event::Event,
-) {
+fn handle_system_events(gcontext: &mut GContext, event: &sdl2::event::Event) {
   match *event {
-    Event::Quit { .. } => *want_to_quit = true,
+    Event::Quit { .. } => gcontext.want_to_quit = true,
     #[cfg(not(target_os = "emscripten"))]
     Event::Window {
       win_event: sdl2::event::WindowEvent::Resized(w, h),
       ..
-    } => *window_size = V2U::new(w.try_into().unwrap(), h.try_into().unwrap()),
+    } => gcontext.window_size = V2U::new(w.max(0) as u32, h.max(0) as u32),
+    Event::ControllerDeviceAdded { which, .. } => gcontext.open_controller(which),
+    Event::ControllerDeviceRemoved { which, .. } => gcontext.close_controller(which),
+    #[cfg(not(target_os = "emscripten"))]
     Event::KeyDown {
-      keycode: Some(keycode),
+      keycode: Some(sdl2::keyboard::Keycode::F12),
       ..
     } => {
-      #[cfg(not(target_os = "emscripten"))]
-      if keycode == sdl2::keyboard::Keycode::F12 {
-        GContext::take_screenshot(canvas)
+      if let Some(display) = &gcontext.display {
+        if let Err(e) = GContext::take_screenshot(&display.canvas) {
+          log_error(e);
+        }
       }
-      key_status.set_key_pressed(keycode, true);
-    }
-    Event::KeyUp {
-      keycode: Some(keycode),
-      ..
-    } => {
-      key_status.set_key_pressed(keycode, false);
     }
     _ => {}
   }
@@ -0,0 +1,226 @@
+use crate::game_state::*;
+use crate::gcontext::*;
+use crate::renderer::*;
+
+// How the screen changes from one scene to the next. The old scene is covered during the
+// first half of the ticks, the scene change happens in the middle and the new scene is
+// uncovered during the second half.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Transition {
+  None,
+  // fades to the background color in a few steps, like an lcd losing contrast
+  Fade { ticks: u32 },
+  // covers the screen with the background color from the left, then uncovers it
+  Wipe { ticks: u32 },
+}
+
+impl Transition {
+  fn ticks(&self) -> u32 {
+    match *self {
+      Transition::None => 0,
+      Transition::Fade { ticks } | Transition::Wipe { ticks } => ticks,
+    }
+  }
+}
+
+pub enum SceneChange {
+  Push(Box<dyn GameState>),
+  Pop,
+  Replace(Box<dyn GameState>),
+  ReplaceAll(Box<dyn GameState>),
+}
+
+struct RunningTransition {
+  transition: Transition,
+  elapsed: u32,
+  change: Option<SceneChange>,
+}
+
+impl RunningTransition {
+  fn ticks_out(&self) -> u32 {
+    let ticks = self.transition.ticks();
+    ticks - ticks / 2
+  }
+
+  // how much of the screen is covered, from 0 to 1
+  fn amount(&self) -> f32 {
+    let ticks = self.transition.ticks();
+    let ticks_out = self.ticks_out();
+    if self.elapsed <= ticks_out {
+      self.elapsed as f32 / ticks_out as f32
+    } else {
+      (ticks - self.elapsed) as f32 / (ticks - ticks_out) as f32
+    }
+  }
+}
+
+// The scenes of a game, only the top one gets events and updates. Scenes below an overlay
+// are still rendered. Nothing is updated while a transition runs, so input during it is
+// dropped.
+pub struct SceneStack {
+  scenes: Vec<Box<dyn GameState>>,
+  running: Option<RunningTransition>,
+}
+
+impl SceneStack {
+  pub fn new() -> SceneStack {
+    SceneStack {
+      scenes: Vec::new(),
+      running: None,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.scenes.is_empty()
+  }
+
+  pub fn is_transitioning(&self) -> bool {
+    self.running.is_some()
+  }
+
+  pub fn top(&self) -> Option<&dyn GameState> {
+    self.scenes.last().map(|scene| scene.as_ref())
+  }
+
+  // the scene that gets events and updates, none while a transition runs
+  pub fn active_mut(&mut self) -> Option<&mut dyn GameState> {
+    if self.is_transitioning() {
+      return None;
+    }
+    match self.scenes.last_mut() {
+      Some(scene) => Some(scene.as_mut()),
+      None => None,
+    }
+  }
+
+  pub fn change(&mut self, change: SceneChange, transition: Transition, gcontext: &mut GContext) {
+    // a change that is still waiting for its transition happens right away
+    if let Some(pending) = self.running.take().and_then(|running| running.change) {
+      self.apply(pending, gcontext);
+    }
+    if transition.ticks() == 0 {
+      self.apply(change, gcontext);
+    } else {
+      self.running = Some(RunningTransition {
+        transition,
+        elapsed: 0,
+        change: Some(change),
+      });
+    }
+  }
+
+  fn apply(&mut self, change: SceneChange, gcontext: &mut GContext) {
+    let mut scene = match change {
+      SceneChange::Pop => {
+        self.scenes.pop();
+        return;
+      }
+      SceneChange::Push(scene) => scene,
+      SceneChange::Replace(scene) => {
+        self.scenes.pop();
+        scene
+      }
+      SceneChange::ReplaceAll(scene) => {
+        self.scenes.clear();
+        scene
+      }
+    };
+    scene.init(gcontext);
+    self.scenes.push(scene);
+  }
+
+  // advances a running transition by one game tick
+  pub fn tick(&mut self, gcontext: &mut GContext) {
+    let mut running = match self.running.take() {
+      Some(running) => running,
+      None => return,
+    };
+    running.elapsed += 1;
+    if running.elapsed >= running.ticks_out() {
+      if let Some(change) = running.change.take() {
+        self.apply(change, gcontext);
+      }
+    }
+    if running.elapsed < running.transition.ticks() {
+      self.running = Some(running);
+    }
+  }
+
+  pub fn render(&self, gcontext: &mut GContext) {
+    let mut bottom = self.scenes.len();
+    while bottom > 0 {
+      bottom -= 1;
+      if !self.scenes[bottom].is_overlay() {
+        break;
+      }
+    }
+    for scene in &self.scenes[bottom..] {
+      scene.render(gcontext);
+    }
+    if let Some(running) = &self.running {
+      render_transition(gcontext, running);
+    }
+  }
+
+  pub fn on_quit(&mut self) {
+    for scene in self.scenes.iter_mut().rev() {
+      scene.on_quit();
+    }
+  }
+}
+
+impl Default for SceneStack {
+  fn default() -> SceneStack {
+    SceneStack::new()
+  }
+}
+
+fn render_transition(gcontext: &mut GContext, running: &RunningTransition) {
+  let background_color = gcontext.get_config().background_color;
+  let amount = running.amount();
+  match running.transition {
+    Transition::None => {}
+    Transition::Fade { .. } => {
+      let amount = (amount * 4.0).round() / 4.0;
+      gcontext
+        .target_and_assets()
+        .0
+        .map_pixels(&mut |color| color.lerp(background_color, amount));
+    }
+    Transition::Wipe { .. } => {
+      let size = gcontext.screen_size();
+      let covered = (size.x as f32 * amount).round() as u32;
+      let x = if running.elapsed <= running.ticks_out() {
+        0
+      } else {
+        size.x - covered
+      };
+      gcontext.draw_rect(x as i32, 0, covered, size.y, background_color);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::config::*;
+  use crate::run::*;
+
+  struct Leaving;
+
+  impl GameState for Leaving {
+    fn update(&mut self, ctx: &mut UpdateContext) {
+      ctx.pop_scene(Transition::Fade { ticks: 4 });
+    }
+  }
+
+  #[test]
+  fn quits_after_popping_the_last_scene_with_a_transition() {
+    let mut game = Game::new(cell_phone_config(1).headless(), Leaving).unwrap();
+    for _ in 0..10 {
+      game.game_loop();
+    }
+    assert!(game.scenes().is_empty());
+    assert!(game.gcontext().want_to_quit);
+  }
+}
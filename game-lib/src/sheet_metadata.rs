@@ -0,0 +1,233 @@
+// sprite sheet metadata, as exported by Aseprite or TexturePacker
+//
+// Both list `frames`, either as an array of frames with a `filename` or as an object keyed by
+// it, each with its `frame` rectangle on the sheet. Aseprite adds a `duration` in ms to every
+// frame and `meta.frameTags`, which become animations. Rotated frames are not supported and
+// trimmed frames are drawn without the margin that was trimmed off.
+use crate::animation::*;
+use crate::assets::*;
+use crate::json::*;
+use crate::run::TICK_INTERVAL;
+use crate::types::*;
+
+pub struct SheetFrame {
+  pub name: String,
+  pub pos: P2U,
+  pub size: V2U,
+  pub duration_ms: u32,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TagDirection {
+  Forward,
+  Reverse,
+  PingPong,
+  PingPongReverse,
+}
+
+pub struct SheetTag {
+  pub name: String,
+  // frame indices, inclusive
+  pub from: usize,
+  pub to: usize,
+  pub direction: TagDirection,
+}
+
+pub struct SheetMetadata {
+  // relative to the metadata file
+  pub image: Option<String>,
+  pub frames: Vec<SheetFrame>,
+  pub tags: Vec<SheetTag>,
+}
+
+// what Aseprite uses for frames without a duration
+const DEFAULT_DURATION_MS: u32 = 100;
+
+impl SheetMetadata {
+  // durations are rounded to whole ticks, `frame_ids` are the sprites of `frames` in order
+  pub fn tag_animation(&self, tag: &SheetTag, frame_ids: &[SpriteId]) -> Animation {
+    let (mode, reverse) = match tag.direction {
+      TagDirection::Forward => (PlayMode::Loop, false),
+      TagDirection::Reverse => (PlayMode::Loop, true),
+      TagDirection::PingPong => (PlayMode::PingPong, false),
+      TagDirection::PingPongReverse => (PlayMode::PingPong, true),
+    };
+    let mut frames: Vec<usize> = (tag.from..=tag.to).collect();
+    if reverse {
+      frames.reverse();
+    }
+    frames.iter().fold(Animation::new(mode), |animation, &ix| {
+      let ticks = (self.frames[ix].duration_ms + TICK_INTERVAL / 2) / TICK_INTERVAL;
+      animation.sprite(frame_ids[ix], ticks)
+    })
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SheetError {
+  Json(JsonError),
+  MissingFrames,
+  // the name of the frame or tag
+  InvalidFrame(String),
+  RotatedFrame(String),
+  InvalidTag(String),
+}
+
+impl std::fmt::Display for SheetError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      SheetError::Json(e) => write!(f, "{}", e),
+      SheetError::MissingFrames => write!(f, "missing frames"),
+      SheetError::InvalidFrame(name) => write!(f, "invalid frame '{}'", name),
+      SheetError::RotatedFrame(name) => write!(f, "frame '{}' is rotated", name),
+      SheetError::InvalidTag(name) => write!(f, "invalid frame tag '{}'", name),
+    }
+  }
+}
+
+impl std::error::Error for SheetError {}
+
+pub fn parse_sheet_metadata(text: &str) -> Result<SheetMetadata, SheetError> {
+  let json = parse_json(text).map_err(SheetError::Json)?;
+  let frames = match json.get("frames") {
+    Some(Json::Array(frames)) => frames
+      .iter()
+      .enumerate()
+      .map(|(ix, frame)| {
+        let name = match frame.get("filename").and_then(Json::as_str) {
+          Some(name) => name.to_string(),
+          None => ix.to_string(),
+        };
+        parse_frame(name, frame)
+      })
+      .collect::<Result<Vec<_>, _>>()?,
+    Some(Json::Object(frames)) => frames
+      .iter()
+      .map(|(name, frame)| parse_frame(name.clone(), frame))
+      .collect::<Result<Vec<_>, _>>()?,
+    _ => return Err(SheetError::MissingFrames),
+  };
+
+  let meta = json.get("meta");
+  let image = meta
+    .and_then(|meta| meta.get("image"))
+    .and_then(Json::as_str)
+    .map(str::to_string);
+  let tags = meta
+    .and_then(|meta| meta.get("frameTags"))
+    .and_then(Json::as_array)
+    .unwrap_or(&[])
+    .iter()
+    .map(|tag| parse_tag(tag, frames.len()))
+    .collect::<Result<Vec<_>, _>>()?;
+  Ok(SheetMetadata {
+    image,
+    frames,
+    tags,
+  })
+}
+
+fn parse_frame(name: String, frame: &Json) -> Result<SheetFrame, SheetError> {
+  if frame.get("rotated").and_then(Json::as_bool) == Some(true) {
+    return Err(SheetError::RotatedFrame(name));
+  }
+  let rect = frame.get("frame");
+  let field = |key| rect.and_then(|rect| rect.get(key)).and_then(Json::as_u32);
+  match (field("x"), field("y"), field("w"), field("h")) {
+    (Some(x), Some(y), Some(w), Some(h)) => Ok(SheetFrame {
+      pos: P2U::new(x, y),
+      size: V2U::new(w, h),
+      duration_ms: frame
+        .get("duration")
+        .and_then(Json::as_u32)
+        .unwrap_or(DEFAULT_DURATION_MS),
+      name,
+    }),
+    _ => Err(SheetError::InvalidFrame(name)),
+  }
+}
+
+fn parse_tag(tag: &Json, frame_count: usize) -> Result<SheetTag, SheetError> {
+  let name = tag.get("name").and_then(Json::as_str).unwrap_or_default();
+  let invalid = || SheetError::InvalidTag(name.to_string());
+  let index = |key| {
+    tag
+      .get(key)
+      .and_then(Json::as_u32)
+      .map(|ix| ix as usize)
+      .filter(|ix| *ix < frame_count)
+  };
+  let (from, to) = match (index("from"), index("to")) {
+    (Some(from), Some(to)) if from <= to => (from, to),
+    _ => return Err(invalid()),
+  };
+  let direction = match tag.get("direction").and_then(Json::as_str) {
+    None | Some("forward") => TagDirection::Forward,
+    Some("reverse") => TagDirection::Reverse,
+    Some("pingpong") => TagDirection::PingPong,
+    Some("pingpong_reverse") => TagDirection::PingPongReverse,
+    _ => return Err(invalid()),
+  };
+  Ok(SheetTag {
+    name: name.to_string(),
+    from,
+    to,
+    direction,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::store::*;
+
+  const ASEPRITE: &str = r#"{ "frames": {
+    "hero 0.aseprite": { "frame": { "x": 0, "y": 0, "w": 16, "h": 24 }, "rotated": false,
+      "trimmed": false, "duration": 100 },
+    "hero 1.aseprite": { "frame": { "x": 16, "y": 0, "w": 16, "h": 24 }, "duration": 50 },
+    "hero 2.aseprite": { "frame": { "x": 32, "y": 0, "w": 12, "h": 24 }, "duration": 150 }
+  },
+  "meta": { "image": "hero.png", "size": { "w": 44, "h": 24 },
+    "frameTags": [
+      { "name": "idle", "from": 0, "to": 0, "direction": "forward" },
+      { "name": "walk", "from": 1, "to": 2, "direction": "pingpong" }
+    ] } }"#;
+
+  const TEXTURE_PACKER: &str = r#"{"frames": [
+    {"filename": "coin", "frame": {"x":0,"y":0,"w":8,"h":8}, "rotated": false},
+    {"filename": "gem", "frame": {"x":8,"y":0,"w":6,"h":7}, "rotated": true}
+  ], "meta": {"image": "items.png"}}"#;
+
+  #[test]
+  fn parses_aseprite() {
+    let metadata = parse_sheet_metadata(ASEPRITE).unwrap();
+    assert_eq!(metadata.image.as_deref(), Some("hero.png"));
+    let names: Vec<&str> = metadata.frames.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(
+      names,
+      vec!["hero 0.aseprite", "hero 1.aseprite", "hero 2.aseprite"]
+    );
+    assert_eq!(metadata.frames[2].pos, P2U::new(32, 0));
+    assert_eq!(metadata.frames[2].size, V2U::new(12, 24));
+
+    let walk = &metadata.tags[1];
+    assert_eq!((walk.from, walk.to), (1, 2));
+    let frame_ids: Vec<SpriteId> = (0..3).map(Handle::new).collect();
+    let animation = metadata.tag_animation(walk, &frame_ids);
+    assert_eq!(animation.mode(), PlayMode::PingPong);
+    // 50 and 150 ms are 1 and 3 ticks
+    assert_eq!(animation.pass_ticks(), 4);
+  }
+
+  #[test]
+  fn rejects_rotated_frames() {
+    assert_eq!(
+      parse_sheet_metadata(TEXTURE_PACKER).err(),
+      Some(SheetError::RotatedFrame("gem".to_string()))
+    );
+    let unrotated = TEXTURE_PACKER.replace("true", "false");
+    let metadata = parse_sheet_metadata(&unrotated).unwrap();
+    assert_eq!(metadata.frames[1].size, V2U::new(6, 7));
+    assert_eq!(metadata.frames[1].duration_ms, DEFAULT_DURATION_MS);
+  }
+}
@@ -0,0 +1,146 @@
+// assets by handle and by name
+//
+// Adding an asset returns a handle, a copyable index that is cheap to draw with and can't
+// name a missing asset. Looking an asset up by name can fail, with an error naming it.
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+pub trait AssetName: Eq + Hash + Clone {
+  // e.g. "sprite", for errors
+  const KIND: &'static str;
+  fn as_str(&self) -> &str;
+}
+
+// a handle to an asset named by `N` in the store that returned it
+pub struct Handle<N> {
+  index: u32,
+  marker: PhantomData<fn() -> N>,
+}
+
+impl<N> Handle<N> {
+  pub(crate) fn new(index: u32) -> Handle<N> {
+    Handle {
+      index,
+      marker: PhantomData,
+    }
+  }
+}
+
+// derives would require the same of `N`
+impl<N> Clone for Handle<N> {
+  fn clone(&self) -> Handle<N> {
+    *self
+  }
+}
+
+impl<N> Copy for Handle<N> {}
+
+impl<N> PartialEq for Handle<N> {
+  fn eq(&self, other: &Handle<N>) -> bool {
+    self.index == other.index
+  }
+}
+
+impl<N> Eq for Handle<N> {}
+
+impl<N> Hash for Handle<N> {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.index.hash(state);
+  }
+}
+
+impl<N: AssetName> std::fmt::Debug for Handle<N> {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{} #{}", N::KIND, self.index)
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct AssetError {
+  pub kind: &'static str,
+  pub name: String,
+}
+
+impl std::fmt::Display for AssetError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "unknown {} '{}'", self.kind, self.name)
+  }
+}
+
+impl std::error::Error for AssetError {}
+
+pub struct Store<N, T> {
+  items: Vec<T>,
+  handles: HashMap<N, Handle<N>>,
+}
+
+impl<N: AssetName, T> Store<N, T> {
+  pub fn new() -> Store<N, T> {
+    Store {
+      items: Vec::new(),
+      handles: HashMap::new(),
+    }
+  }
+
+  // adding a name again replaces its asset and keeps its handle
+  pub fn insert(&mut self, name: N, item: T) -> Handle<N> {
+    if let Some(handle) = self.handles.get(&name) {
+      self.items[handle.index as usize] = item;
+      return *handle;
+    }
+    let handle = Handle::new(self.items.len() as u32);
+    self.items.push(item);
+    self.handles.insert(name, handle);
+    handle
+  }
+
+  pub fn get(&self, handle: Handle<N>) -> &T {
+    &self.items[handle.index as usize]
+  }
+
+  pub fn get_mut(&mut self, handle: Handle<N>) -> &mut T {
+    &mut self.items[handle.index as usize]
+  }
+
+  pub fn handle(&self, name: &N) -> Result<Handle<N>, AssetError> {
+    self.handles.get(name).copied().ok_or_else(|| AssetError {
+      kind: N::KIND,
+      name: name.as_str().to_string(),
+    })
+  }
+}
+
+impl<N: AssetName, T> Default for Store<N, T> {
+  fn default() -> Store<N, T> {
+    Store::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Eq, PartialEq, Hash, Clone)]
+  struct ThingName(&'static str);
+
+  impl AssetName for ThingName {
+    const KIND: &'static str = "thing";
+    fn as_str(&self) -> &str {
+      self.0
+    }
+  }
+
+  #[test]
+  fn stores_by_handle_and_name() {
+    let mut store = Store::new();
+    let a = store.insert(ThingName("a"), 1);
+    let b = store.insert(ThingName("b"), 2);
+    assert_ne!(a, b);
+    assert_eq!(store.insert(ThingName("a"), 3), a);
+    assert_eq!(*store.get(a), 3);
+    assert_eq!(store.handle(&ThingName("b")), Ok(b));
+    let error = store.handle(&ThingName("c")).unwrap_err();
+    assert_eq!(error.to_string(), "unknown thing 'c'");
+  }
+}
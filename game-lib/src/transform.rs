@@ -0,0 +1,162 @@
+// flipped, rotated and recolored copies of images, applied when drawing sprites and surfaces
+use crate::color::*;
+use crate::config::*;
+use crate::image::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Rotation {
+  None,
+  // clockwise
+  Quarter,
+  Half,
+  ThreeQuarters,
+}
+
+// flips are applied before the rotation
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DrawOptions {
+  pub flip_x: bool,
+  pub flip_y: bool,
+  pub rotation: Rotation,
+  // colors matching the first of a pair are drawn in the second, keeping their alpha
+  pub palette_swap: Vec<(Color, Color)>,
+}
+
+impl DrawOptions {
+  pub fn new() -> DrawOptions {
+    DrawOptions {
+      flip_x: false,
+      flip_y: false,
+      rotation: Rotation::None,
+      palette_swap: Vec::new(),
+    }
+  }
+
+  pub fn flip_x(self) -> DrawOptions {
+    DrawOptions {
+      flip_x: !self.flip_x,
+      ..self
+    }
+  }
+
+  pub fn flip_y(self) -> DrawOptions {
+    DrawOptions {
+      flip_y: !self.flip_y,
+      ..self
+    }
+  }
+
+  pub fn rotate(self, rotation: Rotation) -> DrawOptions {
+    DrawOptions { rotation, ..self }
+  }
+
+  pub fn swap(mut self, from: Color, to: Color) -> DrawOptions {
+    self.palette_swap.push((from, to));
+    self
+  }
+
+  // e.g. an inverted hit flash
+  pub fn swap_both(self, a: Color, b: Color) -> DrawOptions {
+    self.swap(a, b).swap(b, a)
+  }
+
+  pub fn is_identity(&self) -> bool {
+    *self == DrawOptions::new()
+  }
+}
+
+impl Default for DrawOptions {
+  fn default() -> DrawOptions {
+    DrawOptions::new()
+  }
+}
+
+pub fn transform_image(src: &ImageRegion, options: &DrawOptions) -> Image {
+  let (w, h) = (src.size.x, src.size.y);
+  let (out_w, out_h) = match options.rotation {
+    Rotation::None | Rotation::Half => (w, h),
+    Rotation::Quarter | Rotation::ThreeQuarters => (h, w),
+  };
+  let mut image = Image::new(out_w, out_h);
+  for y in 0..out_h {
+    for x in 0..out_w {
+      // where the pixel comes from in the flipped source
+      let (fx, fy) = match options.rotation {
+        Rotation::None => (x, y),
+        Rotation::Quarter => (y, h - 1 - x),
+        Rotation::Half => (w - 1 - x, h - 1 - y),
+        Rotation::ThreeQuarters => (w - 1 - y, x),
+      };
+      let sx = if options.flip_x { w - 1 - fx } else { fx };
+      let sy = if options.flip_y { h - 1 - fy } else { fy };
+      let color = src
+        .image
+        .get((src.pos.x + sx) as i32, (src.pos.y + sy) as i32)
+        .unwrap_or(TRANSPARENT);
+      *image.get_mut(x as i32, y as i32).unwrap() = swap_color(color, &options.palette_swap);
+    }
+  }
+  image
+}
+
+fn swap_color(color: Color, palette_swap: &[(Color, Color)]) -> Color {
+  let opaque = Color::RGB(color.r, color.g, color.b);
+  match palette_swap.iter().find(|(from, _)| *from == opaque) {
+    Some((_, to)) => Color::RGBA(to.r, to.g, to.b, color.a),
+    None => color,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn transformed(options: DrawOptions) -> Vec<String> {
+    let palette = [('a', Color::WHITE), ('b', Color::BLACK)]
+      .iter()
+      .cloned()
+      .collect();
+    let image = Image::from_strvec(&palette, &["ab ", "   "]);
+    let image = transform_image(&image.region(), &options);
+    (0..image.height() as i32)
+      .map(|y| {
+        (0..image.width() as i32)
+          .map(|x| match image.get(x, y).unwrap() {
+            Color::WHITE => 'a',
+            Color::BLACK => 'b',
+            _ => ' ',
+          })
+          .collect()
+      })
+      .collect()
+  }
+
+  #[test]
+  fn flips_and_rotates() {
+    assert_eq!(transformed(DrawOptions::new().flip_x()), vec![" ba", "   "]);
+    assert_eq!(transformed(DrawOptions::new().flip_y()), vec!["   ", "ab "]);
+    assert_eq!(
+      transformed(DrawOptions::new().rotate(Rotation::Quarter)),
+      vec![" a", " b", "  "]
+    );
+    assert_eq!(
+      transformed(DrawOptions::new().rotate(Rotation::Half)),
+      vec!["   ", " ba"]
+    );
+    assert_eq!(
+      transformed(DrawOptions::new().rotate(Rotation::ThreeQuarters)),
+      vec!["  ", "b ", "a "]
+    );
+    // flipped first, then rotated
+    assert_eq!(
+      transformed(DrawOptions::new().flip_x().rotate(Rotation::Quarter)),
+      vec!["  ", " b", " a"]
+    );
+  }
+
+  #[test]
+  fn swaps_colors() {
+    let options = DrawOptions::new().swap_both(Color::WHITE, Color::BLACK);
+    assert_eq!(transformed(options), vec!["ba ", "   "]);
+  }
+}
@@ -9,7 +9,6 @@ pub type P2F = cgmath::Point2<f32>;
 pub type P3F = cgmath::Point3<f32>;
 pub type P4U8 = cgmath::Vector4<u8>;
 pub type M4F = cgmath::Matrix4<f32>;
-pub type Store<T> = std::collections::HashMap<String, T>;
 
 #[derive(Copy, Clone)]
 pub struct Rect {